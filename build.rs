@@ -0,0 +1,30 @@
+//
+// Build script to embed version information
+//
+
+use std::process::Command;
+
+// Output of the command, "unknown" if not available (e.g. no git in CI).
+fn command_output(cmd: &str, args: &[&str]) -> String {
+    Command::new(cmd)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn main() {
+    println!(
+        "cargo:rustc-env=GIT_HASH={}",
+        command_output("git", &["rev-parse", "--short", "HEAD"])
+    );
+    println!(
+        "cargo:rustc-env=BUILD_DATE={}",
+        command_output("date", &["-u", "+%Y-%m-%d"])
+    );
+    println!("cargo:rerun-if-changed=.git/HEAD");
+}
@@ -1,8 +1,12 @@
 ///
 /// SPI Control parts.
 ///
-use rppal::gpio::OutputPin;
-use rppal::spi::Spi;
+use embedded_hal::digital::OutputPin;
+use embedded_hal::spi::{Operation, SpiDevice};
+use std::fmt;
+
+mod rpi;
+pub use rpi::{RppalSPIInterfaceAutoCS, RppalSPIInterfaceManualCS};
 
 #[derive(Clone, Debug)]
 #[non_exhaustive]
@@ -23,23 +27,65 @@ pub enum DisplayError {
     OutOfBoundsError,
 }
 
+impl fmt::Display for DisplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            DisplayError::InvalidFormatError => "invalid data format",
+            DisplayError::BusWriteError => "unable to write to bus",
+            DisplayError::DCError => "unable to set data/command signal",
+            DisplayError::CSError => "unable to set chip select signal",
+            DisplayError::DataFormatNotImplemented => "data format not implemented",
+            DisplayError::RSError => "unable to set reset signal",
+            DisplayError::OutOfBoundsError => "pixel out of display bounds",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl std::error::Error for DisplayError {}
+
 ///
 /// Data-type definitions.
 ///
 
+// SPI clock mode of the display.
+// Mode3 (CPOL=1, CPHA=1) is typical for ST7789 boards,
+// Mode0 (CPOL=0, CPHA=0) is used by some adapters (e.g. for SH1106).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SpiMode {
+    Mode0,
+    Mode3,
+}
+
 // Use this if default CS used for specific spi.
+// SPI is any embedded-hal SpiDevice, DC any OutputPin (see RppalSPIInterfaceAutoCS).
 #[derive(Debug)]
-pub struct SPIInterfaceAutoCS {
-    spi: Spi,
-    dc: OutputPin,
+pub struct SPIInterfaceAutoCS<SPI, DC> {
+    spi: SPI,
+    dc: DC,
 }
 
 // Use this if not default CS port is need to switch manually.
 // Note: Never use this if CS is default for spi.
 #[derive(Debug)]
-pub struct SPIInterfaceManualCS {
-    spi_no_cs: SPIInterfaceAutoCS,
-    cs: OutputPin,
+pub struct SPIInterfaceManualCS<SPI, DC, CS> {
+    spi_no_cs: SPIInterfaceAutoCS<SPI, DC>,
+    cs: CS,
+}
+
+// Use this to run without display. Writes are only counted,
+// unless made with MockInterface::recording (for tests).
+#[derive(Debug, Default)]
+pub struct MockInterface {
+    bytes_written: usize,
+    ops: Option<Vec<MockOp>>,
+}
+
+// A write recorded by MockInterface.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MockOp {
+    Command(u8),
+    Data(Vec<u8>),
 }
 
 pub trait WriteOnlyDataCommand {
@@ -48,10 +94,101 @@ pub trait WriteOnlyDataCommand {
 
     /// Send pixel data to display
     fn send_data(&mut self, data: &[u8]) -> Result<(), DisplayError>;
+
+    /// Send a command followed by its parameters
+    fn write_command_data_batched(&mut self, cmd: u8, data: &[u8]) -> Result<(), DisplayError> {
+        self.send_command(cmd)?;
+        self.send_data(data)
+    }
+}
+
+// Needs MISO (SDA of 3-wire displays is not supported).
+pub trait ReadDataCommand {
+    /// Send a read command and receive its parameters
+    fn read_data(&mut self, cmd: u8, buf: &mut [u8]) -> Result<(), DisplayError>;
+}
+
+// Interface both writing and reading, usable as a trait object.
+pub trait DataCommand: WriteOnlyDataCommand + ReadDataCommand {}
+
+impl<T: WriteOnlyDataCommand + ReadDataCommand + ?Sized> DataCommand for T {}
+
+impl<T: WriteOnlyDataCommand + ?Sized> WriteOnlyDataCommand for Box<T> {
+    fn send_command(&mut self, cmd: u8) -> Result<(), DisplayError> {
+        (**self).send_command(cmd)
+    }
+
+    fn send_data(&mut self, data: &[u8]) -> Result<(), DisplayError> {
+        (**self).send_data(data)
+    }
+
+    fn write_command_data_batched(&mut self, cmd: u8, data: &[u8]) -> Result<(), DisplayError> {
+        (**self).write_command_data_batched(cmd, data)
+    }
+}
+
+impl<T: ReadDataCommand + ?Sized> ReadDataCommand for Box<T> {
+    fn read_data(&mut self, cmd: u8, buf: &mut [u8]) -> Result<(), DisplayError> {
+        (**self).read_data(cmd, buf)
+    }
 }
 
-impl SPIInterfaceManualCS {
-    pub fn new(spi: Spi, dc: OutputPin, cs: OutputPin) -> Self {
+impl MockInterface {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Also keep every command and data write in order.
+    pub fn recording() -> Self {
+        Self {
+            ops: Some(Vec::new()),
+            ..Self::default()
+        }
+    }
+
+    // Writes recorded so far (always empty if not recording).
+    pub fn ops(&self) -> &[MockOp] {
+        self.ops.as_deref().unwrap_or_default()
+    }
+
+    // Total bytes of commands and data written so far.
+    pub fn bytes_written(&self) -> usize {
+        self.bytes_written
+    }
+}
+
+impl WriteOnlyDataCommand for MockInterface {
+    fn send_command(&mut self, cmd: u8) -> Result<(), DisplayError> {
+        self.bytes_written += 1;
+        if let Some(ops) = self.ops.as_mut() {
+            ops.push(MockOp::Command(cmd));
+        }
+        Ok(())
+    }
+
+    fn send_data(&mut self, data: &[u8]) -> Result<(), DisplayError> {
+        self.bytes_written += data.len();
+        if let Some(ops) = self.ops.as_mut() {
+            ops.push(MockOp::Data(data.to_vec()));
+        }
+        Ok(())
+    }
+}
+
+// Reads as all zero, recorded as the read command.
+impl ReadDataCommand for MockInterface {
+    fn read_data(&mut self, cmd: u8, buf: &mut [u8]) -> Result<(), DisplayError> {
+        self.send_command(cmd)?;
+        buf.fill(0);
+        Ok(())
+    }
+}
+
+impl<SPI, DC, CS> SPIInterfaceManualCS<SPI, DC, CS>
+where
+    CS: OutputPin,
+{
+    pub fn new(spi: SPI, dc: DC, cs: CS) -> Self {
         Self {
             spi_no_cs: SPIInterfaceAutoCS::new(spi, dc),
             cs,
@@ -61,21 +198,26 @@ impl SPIInterfaceManualCS {
     /// SPI operation with manual gpio switch.
     fn with_cs(
         &mut self,
-        f: impl FnOnce(&mut SPIInterfaceAutoCS) -> Result<(), DisplayError>,
+        f: impl FnOnce(&mut SPIInterfaceAutoCS<SPI, DC>) -> Result<(), DisplayError>,
     ) -> Result<(), DisplayError> {
         // Assert chip select pin
-        self.cs.set_low();
+        self.cs.set_low().map_err(|_| DisplayError::CSError)?;
 
         let result = f(&mut self.spi_no_cs);
 
         // Deassert chip select pin
-        self.cs.set_high();
+        self.cs.set_high().map_err(|_| DisplayError::CSError)?;
 
         result
     }
 }
 
-impl WriteOnlyDataCommand for SPIInterfaceManualCS {
+impl<SPI, DC, CS> WriteOnlyDataCommand for SPIInterfaceManualCS<SPI, DC, CS>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+    CS: OutputPin,
+{
     fn send_command(&mut self, cmd: u8) -> Result<(), DisplayError> {
         self.with_cs(|spi_no_cs| spi_no_cs.send_command(cmd))
     }
@@ -83,17 +225,52 @@ impl WriteOnlyDataCommand for SPIInterfaceManualCS {
     fn send_data(&mut self, buf: &[u8]) -> Result<(), DisplayError> {
         self.with_cs(|spi_no_cs| spi_no_cs.send_data(buf))
     }
+
+    fn write_command_data_batched(&mut self, cmd: u8, data: &[u8]) -> Result<(), DisplayError> {
+        self.with_cs(|spi_no_cs| spi_no_cs.write_command_data_batched(cmd, data))
+    }
 }
 
-impl SPIInterfaceAutoCS {
-    pub fn new(spi: Spi, dc: OutputPin) -> Self {
+impl<SPI, DC> SPIInterfaceAutoCS<SPI, DC> {
+    pub fn new(spi: SPI, dc: DC) -> Self {
         Self { spi, dc }
     }
 }
 
-impl WriteOnlyDataCommand for SPIInterfaceAutoCS {
+impl<SPI, DC> ReadDataCommand for SPIInterfaceAutoCS<SPI, DC>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+{
+    // The command and the read must be in one transaction,
+    // as the display ends the read command when CS is deasserted.
+    fn read_data(&mut self, cmd: u8, buf: &mut [u8]) -> Result<(), DisplayError> {
+        self.dc.set_low().map_err(|_| DisplayError::DCError)?;
+        self.spi
+            .transaction(&mut [Operation::Write(&[cmd]), Operation::Read(buf)])
+            .map_err(|_| DisplayError::BusWriteError)?;
+        Ok(())
+    }
+}
+
+impl<SPI, DC, CS> ReadDataCommand for SPIInterfaceManualCS<SPI, DC, CS>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+    CS: OutputPin,
+{
+    fn read_data(&mut self, cmd: u8, buf: &mut [u8]) -> Result<(), DisplayError> {
+        self.with_cs(|spi_no_cs| spi_no_cs.read_data(cmd, buf))
+    }
+}
+
+impl<SPI, DC> WriteOnlyDataCommand for SPIInterfaceAutoCS<SPI, DC>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+{
     fn send_command(&mut self, cmd: u8) -> Result<(), DisplayError> {
-        self.dc.set_low();
+        self.dc.set_low().map_err(|_| DisplayError::DCError)?;
         self.spi
             .write(&[cmd])
             .map_err(|_| DisplayError::BusWriteError)?;
@@ -102,7 +279,20 @@ impl WriteOnlyDataCommand for SPIInterfaceAutoCS {
 
     fn send_data(&mut self, data: &[u8]) -> Result<(), DisplayError> {
         // Set DI low for command, high for data.
-        self.dc.set_high();
+        self.dc.set_high().map_err(|_| DisplayError::DCError)?;
+        self.spi
+            .write(data)
+            .map_err(|_| DisplayError::BusWriteError)?;
+        Ok(())
+    }
+
+    // DC low for the command byte, and high once for all parameter bytes.
+    fn write_command_data_batched(&mut self, cmd: u8, data: &[u8]) -> Result<(), DisplayError> {
+        self.dc.set_low().map_err(|_| DisplayError::DCError)?;
+        self.spi
+            .write(&[cmd])
+            .map_err(|_| DisplayError::BusWriteError)?;
+        self.dc.set_high().map_err(|_| DisplayError::DCError)?;
         self.spi
             .write(data)
             .map_err(|_| DisplayError::BusWriteError)?;
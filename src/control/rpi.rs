@@ -0,0 +1,83 @@
+///
+/// SPI Control parts on Raspberry Pi (rppal).
+///
+use super::{
+    DisplayError, ReadDataCommand, SPIInterfaceAutoCS, SPIInterfaceManualCS, SpiMode,
+    WriteOnlyDataCommand,
+};
+use rppal::gpio::OutputPin;
+use rppal::spi::{self, SimpleHalSpiDevice, Spi};
+
+// SPI interfaces with rppal Spi and GPIO pins, wrapping the generic ones.
+// new takes the same arguments as before they became generic.
+// spi should be opened in the mode of the display (see SpiMode).
+pub struct RppalSPIInterfaceAutoCS(SPIInterfaceAutoCS<SimpleHalSpiDevice<Spi>, OutputPin>);
+
+pub struct RppalSPIInterfaceManualCS(
+    SPIInterfaceManualCS<SimpleHalSpiDevice<Spi>, OutputPin, OutputPin>,
+);
+
+impl From<SpiMode> for spi::Mode {
+    fn from(mode: SpiMode) -> Self {
+        match mode {
+            SpiMode::Mode0 => spi::Mode::Mode0,
+            SpiMode::Mode3 => spi::Mode::Mode3,
+        }
+    }
+}
+
+impl RppalSPIInterfaceAutoCS {
+    pub fn new(spi: Spi, dc: OutputPin) -> Self {
+        Self(SPIInterfaceAutoCS::new(SimpleHalSpiDevice::new(spi), dc))
+    }
+}
+
+impl RppalSPIInterfaceManualCS {
+    pub fn new(spi: Spi, dc: OutputPin, cs: OutputPin) -> Self {
+        Self(SPIInterfaceManualCS::new(
+            SimpleHalSpiDevice::new(spi),
+            dc,
+            cs,
+        ))
+    }
+}
+
+impl WriteOnlyDataCommand for RppalSPIInterfaceAutoCS {
+    fn send_command(&mut self, cmd: u8) -> Result<(), DisplayError> {
+        self.0.send_command(cmd)
+    }
+
+    fn send_data(&mut self, buf: &[u8]) -> Result<(), DisplayError> {
+        self.0.send_data(buf)
+    }
+
+    fn write_command_data_batched(&mut self, cmd: u8, data: &[u8]) -> Result<(), DisplayError> {
+        self.0.write_command_data_batched(cmd, data)
+    }
+}
+
+impl ReadDataCommand for RppalSPIInterfaceAutoCS {
+    fn read_data(&mut self, cmd: u8, buf: &mut [u8]) -> Result<(), DisplayError> {
+        self.0.read_data(cmd, buf)
+    }
+}
+
+impl WriteOnlyDataCommand for RppalSPIInterfaceManualCS {
+    fn send_command(&mut self, cmd: u8) -> Result<(), DisplayError> {
+        self.0.send_command(cmd)
+    }
+
+    fn send_data(&mut self, buf: &[u8]) -> Result<(), DisplayError> {
+        self.0.send_data(buf)
+    }
+
+    fn write_command_data_batched(&mut self, cmd: u8, data: &[u8]) -> Result<(), DisplayError> {
+        self.0.write_command_data_batched(cmd, data)
+    }
+}
+
+impl ReadDataCommand for RppalSPIInterfaceManualCS {
+    fn read_data(&mut self, cmd: u8, buf: &mut [u8]) -> Result<(), DisplayError> {
+        self.0.read_data(cmd, buf)
+    }
+}
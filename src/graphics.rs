@@ -0,0 +1,106 @@
+///
+/// embedded-graphics support.
+///
+use crate::control::WriteOnlyDataCommand;
+use crate::{ColorDepth, Error, St7789, CHUNK_SIZE, ST7789_RAMWR};
+use embedded_graphics_core::draw_target::DrawTarget;
+use embedded_graphics_core::geometry::{Dimensions, OriginDimensions, Size};
+use embedded_graphics_core::pixelcolor::{IntoStorage, Rgb565};
+use embedded_graphics_core::primitives::Rectangle;
+use embedded_graphics_core::Pixel;
+use std::iter;
+
+impl<DI> St7789<DI>
+where
+    DI: WriteOnlyDataCommand,
+{
+    // Stream Rgb565 colors into the area (already clipped by the display).
+    // Pixels are batched into CHUNK_SIZE writes, so there is nothing left to flush.
+    fn write_area<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = u16>,
+    {
+        let Some(bottom_right) = area.bottom_right() else {
+            return Ok(());
+        };
+        if self.color_depth != ColorDepth::Bits16 {
+            return Err(Error::DisplayError);
+        }
+        let (x0, y0) = (area.top_left.x as u16, area.top_left.y as u16);
+        let (x1, y1) = (bottom_right.x as u16, bottom_right.y as u16);
+        self.set_window(self.x0 + x0, self.y0 + y0, self.x0 + x1, self.y0 + y1)?;
+
+        self.send_command(ST7789_RAMWR)?; // Write to RAM
+        let mut buf = Vec::with_capacity(CHUNK_SIZE as usize);
+        let n = area.size.width as usize * area.size.height as usize;
+        for color in colors.into_iter().take(n) {
+            buf.extend_from_slice(&color.to_be_bytes());
+            if buf.len() == buf.capacity() {
+                self.send_data(&buf)?;
+                buf.clear();
+            }
+        }
+        if !buf.is_empty() {
+            self.send_data(&buf)?;
+        }
+        Ok(())
+    }
+}
+
+// Size of the display window (same coordinates as draw_pixel).
+impl<DI> OriginDimensions for St7789<DI>
+where
+    DI: WriteOnlyDataCommand,
+{
+    fn size(&self) -> Size {
+        Size::new(
+            (self.x1 - self.x0 + 1) as u32,
+            (self.y1 - self.y0 + 1) as u32,
+        )
+    }
+}
+
+// Draw on the hardware directly. Pixels outside of the display are ignored.
+impl<DI> DrawTarget for St7789<DI>
+where
+    DI: WriteOnlyDataCommand,
+{
+    type Color = Rgb565;
+    type Error = Error;
+
+    // Slow: the window is set for each pixel. Prefer fill_contiguous.
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let bounds = self.bounding_box();
+        for Pixel(point, color) in pixels {
+            if bounds.contains(point) {
+                self.draw_pixel(point.x as u16, point.y as u16, color.into_storage())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let clipped = area.intersection(&self.bounding_box());
+        if clipped == *area {
+            self.write_area(area, colors.into_iter().map(|c| c.into_storage()))
+        } else {
+            let pixels = area.points().zip(colors).map(|(p, c)| Pixel(p, c));
+            self.draw_iter(pixels)
+        }
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let clipped = area.intersection(&self.bounding_box());
+        self.write_area(&clipped, iter::repeat(color.into_storage()))
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_screen(color.into_storage())
+    }
+}
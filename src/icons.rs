@@ -0,0 +1,143 @@
+//! 16x16 pixel art icons for media types
+
+use image::{Rgba, RgbaImage};
+
+pub const ICON_SIZE: u32 = 16;
+
+pub type Icon = [[bool; ICON_SIZE as usize]; ICON_SIZE as usize];
+
+/// Convert row patterns (MSB is the left end) to icon bitmap.
+const fn bitmap(rows: [u16; ICON_SIZE as usize]) -> Icon {
+    let mut icon = [[false; ICON_SIZE as usize]; ICON_SIZE as usize];
+    let mut y = 0;
+    while y < ICON_SIZE as usize {
+        let mut x = 0;
+        while x < ICON_SIZE as usize {
+            icon[y][x] = rows[y] & (0x8000u16 >> x) != 0;
+            x += 1;
+        }
+        y += 1;
+    }
+    icon
+}
+
+pub const ICON_FLAC: Icon = bitmap([
+    0b0111111111111110,
+    0b0000000000000000,
+    0b0000000000000000,
+    0b1110100001000110,
+    0b1110100001000110,
+    0b1000100010101000,
+    0b1000100010101000,
+    0b1100100011101000,
+    0b1100100011101000,
+    0b1000100010101000,
+    0b1000100010101000,
+    0b1000111010100110,
+    0b1000111010100110,
+    0b0000000000000000,
+    0b0000000000000000,
+    0b0111111111111110,
+]);
+
+pub const ICON_MP3: Icon = bitmap([
+    0b0111111111111110,
+    0b0000000000000000,
+    0b0000000000000000,
+    0b0010101100110000,
+    0b0010101100110000,
+    0b0011101010001000,
+    0b0011101010001000,
+    0b0011101100010000,
+    0b0011101100010000,
+    0b0010101000001000,
+    0b0010101000001000,
+    0b0010101000110000,
+    0b0010101000110000,
+    0b0000000000000000,
+    0b0000000000000000,
+    0b0111111111111110,
+]);
+
+pub const ICON_DSD: Icon = bitmap([
+    0b0111111111111110,
+    0b0000000000000000,
+    0b0000000000000000,
+    0b0011000110110000,
+    0b0011000110110000,
+    0b0010101000101000,
+    0b0010101000101000,
+    0b0010100100101000,
+    0b0010100100101000,
+    0b0010100010101000,
+    0b0010100010101000,
+    0b0011001100110000,
+    0b0011001100110000,
+    0b0000000000000000,
+    0b0000000000000000,
+    0b0111111111111110,
+]);
+
+pub const ICON_WAV: Icon = bitmap([
+    0b0111111111111110,
+    0b0000000000000000,
+    0b0000000000000000,
+    0b0010100100101000,
+    0b0010100100101000,
+    0b0010101010101000,
+    0b0010101010101000,
+    0b0011101110101000,
+    0b0011101110101000,
+    0b0011101010101000,
+    0b0011101010101000,
+    0b0010101010010000,
+    0b0010101010010000,
+    0b0000000000000000,
+    0b0000000000000000,
+    0b0111111111111110,
+]);
+
+pub const ICON_AAC: Icon = bitmap([
+    0b0111111111111110,
+    0b0000000000000000,
+    0b0000000000000000,
+    0b0001000100011000,
+    0b0001000100011000,
+    0b0010101010100000,
+    0b0010101010100000,
+    0b0011101110100000,
+    0b0011101110100000,
+    0b0010101010100000,
+    0b0010101010100000,
+    0b0010101010011000,
+    0b0010101010011000,
+    0b0000000000000000,
+    0b0000000000000000,
+    0b0111111111111110,
+]);
+
+/// Select icon for Volumio trackType.
+pub fn icon_for_track_type(track_type: &str) -> Option<&'static Icon> {
+    match track_type.to_ascii_lowercase().as_str() {
+        "flac" => Some(&ICON_FLAC),
+        "mp3" => Some(&ICON_MP3),
+        "dsf" | "dff" | "dsd" => Some(&ICON_DSD),
+        "wav" => Some(&ICON_WAV),
+        "aac" | "m4a" => Some(&ICON_AAC),
+        _ => None,
+    }
+}
+
+/// Draw icon. Only pixels set in the bitmap are drawn.
+pub fn draw_icon(img: &mut RgbaImage, icon: &Icon, x: i32, y: i32, fg: Rgba<u8>) {
+    for (j, row) in icon.iter().enumerate() {
+        for (i, &dot) in row.iter().enumerate() {
+            let px = x + i as i32;
+            let py = y + j as i32;
+            if dot && px >= 0 && py >= 0 && (px as u32) < img.width() && (py as u32) < img.height()
+            {
+                img.put_pixel(px as u32, py as u32, fg);
+            }
+        }
+    }
+}
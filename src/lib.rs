@@ -3,11 +3,16 @@
 //
 
 pub mod control;
+#[cfg(feature = "embedded-graphics")]
+mod graphics;
 
-use crate::control::WriteOnlyDataCommand;
-use image::RgbaImage;
-use rppal::gpio::OutputPin;
-use std::{cmp, thread, time::Duration};
+use crate::control::{ReadDataCommand, WriteOnlyDataCommand};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::imageops::FilterType;
+use image::{imageops, ColorType, DynamicImage, Rgba, RgbaImage};
+use rppal::gpio::{InputPin, OutputPin, Trigger};
+use std::{cmp, fmt, io::Cursor, thread, time::Duration};
 
 ///
 /// Constants
@@ -17,6 +22,8 @@ const ST7789_NOP: u8 = 0x00;
 const ST7789_SWRESET: u8 = 0x01;
 const ST7789_RDDID: u8 = 0x04;
 const ST7789_RDDST: u8 = 0x09;
+const ST7789_RDDMADCTL: u8 = 0x0B;
+const ST7789_RDDCOLMOD: u8 = 0x0C;
 
 const ST7789_SLPIN: u8 = 0x10;
 const ST7789_SLPOUT: u8 = 0x11;
@@ -73,6 +80,89 @@ const ST7789_GMCTRN1: u8 = 0xE1;
 const ST7789_PWCTR6: u8 = 0xFC;
 
 const CHUNK_SIZE: u32 = 4096;
+// Lines of the frame memory (240x320)
+const FRAME_LINES: u16 = 320;
+// Wait for TE at most about 2 frames (60Hz), in case TE is not connected
+const TE_TIMEOUT_MSEC: u64 = 34;
+// Size of tiles for the dirty tracking of St7789Img
+const DIRTY_TILE: u32 = 8;
+
+// Gamma presets for --gamma-preset.
+// 1: Pimoroni / Waveshare 1.3inch 240x240 (ST7789VW)
+pub const GAMMA_PRESET_1: Gamma = Gamma {
+    positive: [
+        0xD0, 0x04, 0x0D, 0x11, 0x13, 0x2B, 0x3F, 0x54, 0x4C, 0x18, 0x0D, 0x0B, 0x1F, 0x23,
+    ],
+    negative: [
+        0xD0, 0x04, 0x0C, 0x11, 0x13, 0x2C, 0x3F, 0x44, 0x51, 0x2F, 0x1F, 0x1F, 0x20, 0x23,
+    ],
+};
+// 2: Waveshare 2inch 240x320 (ST7789V)
+pub const GAMMA_PRESET_2: Gamma = Gamma {
+    positive: [
+        0xD0, 0x08, 0x11, 0x08, 0x0C, 0x15, 0x39, 0x33, 0x50, 0x36, 0x13, 0x14, 0x29, 0x2D,
+    ],
+    negative: [
+        0xD0, 0x08, 0x10, 0x08, 0x06, 0x06, 0x39, 0x44, 0x51, 0x0B, 0x16, 0x14, 0x2F, 0x31,
+    ],
+};
+// 3: Waveshare 1.54inch 240x240 (ST7789)
+pub const GAMMA_PRESET_3: Gamma = Gamma {
+    positive: [
+        0xD0, 0x00, 0x02, 0x07, 0x0A, 0x28, 0x32, 0x44, 0x42, 0x06, 0x0E, 0x12, 0x14, 0x17,
+    ],
+    negative: [
+        0xD0, 0x00, 0x02, 0x07, 0x0A, 0x28, 0x31, 0x54, 0x47, 0x0E, 0x1C, 0x17, 0x1B, 0x1E,
+    ],
+};
+// 4: Waveshare 1.69inch 240x280 (ST7789V2)
+pub const GAMMA_PRESET_4: Gamma = Gamma {
+    positive: [
+        0xD0, 0x08, 0x0E, 0x09, 0x09, 0x05, 0x31, 0x33, 0x48, 0x17, 0x14, 0x15, 0x31, 0x34,
+    ],
+    negative: [
+        0xD0, 0x08, 0x0E, 0x09, 0x09, 0x15, 0x31, 0x33, 0x48, 0x17, 0x14, 0x15, 0x31, 0x34,
+    ],
+};
+
+// Power-on defaults of GMCTRP1 and GMCTRN1 (ST7789V datasheet).
+pub const GAMMA_POWER_ON: Gamma = Gamma {
+    positive: [
+        0xD0, 0x00, 0x02, 0x07, 0x0B, 0x1A, 0x31, 0x54, 0x40, 0x29, 0x12, 0x12, 0x12, 0x17,
+    ],
+    negative: [
+        0xD0, 0x00, 0x02, 0x07, 0x05, 0x25, 0x2D, 0x44, 0x45, 0x1C, 0x18, 0x16, 0x1C, 0x1D,
+    ],
+};
+
+// Power-on defaults of VRHS (GVDD 4.1V) and VDVS (VDV 0V).
+pub const DEF_VRHS: u8 = 0x0B;
+pub const DEF_VDVS: u8 = 0x20;
+// Common GVDD voltages for VRHS: 3.55V + 0.05V * value.
+pub const VRHS_TABLE: [(f32, u8); 6] = [
+    (3.80, 0x05),
+    (4.10, 0x0B),
+    (4.35, 0x10),
+    (4.45, 0x12),
+    (4.60, 0x15),
+    (4.80, 0x19),
+];
+// Common VDV voltages for VDVS: -0.8V + 0.025V * value.
+pub const VDVS_TABLE: [(f32, u8); 5] = [
+    (-0.20, 0x18),
+    (-0.10, 0x1C),
+    (0.00, 0x20),
+    (0.10, 0x24),
+    (0.20, 0x28),
+];
+
+// Register value for the voltage in VRHS_TABLE or VDVS_TABLE.
+pub fn register_for_voltage(table: &[(f32, u8)], volts: f32) -> Option<u8> {
+    table
+        .iter()
+        .find(|(v, _)| (v - volts).abs() < 0.005)
+        .map(|&(_, value)| value)
+}
 
 ///
 /// Data-type definitions.
@@ -83,6 +173,54 @@ pub enum Error {
     DisplayError,
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DisplayError => f.write_str("display error"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Color = Rgba<u8>;
+
+// Called synchronously when writing an image fails. Must be fast.
+pub type ErrorCallback = Box<dyn Fn(&Error) + Send + 'static>;
+
+struct OnDisplayError(ErrorCallback);
+
+impl fmt::Debug for OnDisplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnDisplayError")
+    }
+}
+
+// Parameters of GMCTRP1 (positive) and GMCTRN1 (negative).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Gamma {
+    pub positive: [u8; 14],
+    pub negative: [u8; 14],
+}
+
+// Named gamma curves for set_gamma_curve.
+// Default: power-on values (init does not set gamma).
+// Srgb: GAMMA_PRESET_1, the values of the Pimoroni / Waveshare 1.3inch init code.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GammaCurve {
+    Default,
+    Srgb,
+}
+
+impl GammaCurve {
+    pub fn gamma(self) -> Gamma {
+        match self {
+            GammaCurve::Default => GAMMA_POWER_ON,
+            GammaCurve::Srgb => GAMMA_PRESET_1,
+        }
+    }
+}
+
 #[repr(u8)]
 #[derive(Copy, Clone, Debug)]
 pub enum ROTATION {
@@ -92,11 +230,52 @@ pub enum ROTATION {
     Rot270 = 0xa0u8,
 }
 
+// Settings of St7789 given by name. build() checks them and makes St7789.
+// Defaults: 240x240, Rot0, inverted (for IPS panels), no reset, backlight and TE pins.
+#[derive(Debug)]
+pub struct St7789Builder {
+    pin_rst: Option<OutputPin>,
+    pin_backlight: Option<OutputPin>,
+    pin_te: Option<InputPin>,
+    width: u32,
+    height: u32,
+    rotation: ROTATION,
+    inverted: bool,
+}
+
+// Output of the TE (tearing effect) line (TEON parameter).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TearingMode {
+    VBlankOnly = 0x00,
+    VAndHBlank = 0x01,
+}
+
+// Bytes per coordinate in CASET/RASET.
+// Bit16 is the standard ST7789. Bit8 is for some small (< 256px) variants.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AddressingMode {
+    Bit8,
+    Bit16,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColorDepth {
+    Bits12, // RGB444
+    Bits16, // RGB565
+    Bits18, // RGB666
+}
+
 #[derive(Debug)]
 pub struct St7789Img {
     width: u32,
     height: u32,
+    depth: ColorDepth,
     img_buff: Vec<u8>,
+    // CRC32 of the image given to the last compare_and_update.
+    // None once the buffer is written by anything else.
+    content_hash: Option<u32>,
+    // Bitmask of DIRTY_TILE x DIRTY_TILE tiles written since the last clear_dirty.
+    dirty_tiles: Vec<u64>,
 }
 
 #[derive(Debug)]
@@ -107,35 +286,282 @@ where
     di: DI,
     pin_rst: Option<OutputPin>,
     pin_backlight: Option<OutputPin>,
+    pin_te: Option<InputPin>,
+    tearing_mode: Option<TearingMode>,
     width: u32,
     height: u32,
     rotation: ROTATION,
+    inverted: bool,
+    color_depth: ColorDepth,
+    addressing_mode: AddressingMode,
+    lcm_ctrl: Option<u8>,
+    gctrl: Option<u8>,
+    // Top fixed, scroll and bottom fixed lines of VSCRDER
+    scroll_area: (u16, u16, u16),
+    vrhs: Option<u8>,
+    vdvs: Option<u8>,
+    gamma: Option<Gamma>,
+    x_trim: u16,
+    y_trim: u16,
     x0: u16,
     y0: u16,
     x1: u16,
     y1: u16,
+    on_display_error: Option<OnDisplayError>,
+}
+
+impl ColorDepth {
+    // Bits to transfer per pixel. 18bit color is sent as 3 bytes.
+    pub fn bits_per_pixel(self) -> u32 {
+        match self {
+            ColorDepth::Bits12 => 12,
+            ColorDepth::Bits16 => 16,
+            ColorDepth::Bits18 => 24,
+        }
+    }
+
+    // Parameter for COLMOD
+    fn colmod(self) -> u8 {
+        match self {
+            ColorDepth::Bits12 => 0x53u8, // 12bit 4k color
+            ColorDepth::Bits16 => 0x55u8, // 16bit 65k color
+            ColorDepth::Bits18 => 0x66u8, // 18bit 262k color
+        }
+    }
+
+    // Buffer size for the image
+    fn buff_len(self, width: u32, height: u32) -> usize {
+        (width * height * self.bits_per_pixel()).div_ceil(8) as usize
+    }
 }
 
 impl St7789Img {
-    pub fn new(width: u32, height: u32) -> Self {
+    pub fn new(width: u32, height: u32, depth: ColorDepth) -> Self {
         Self {
             width,
             height,
-            img_buff: vec![0; (width * height * 2) as usize],
+            depth,
+            img_buff: vec![0; depth.buff_len(width, height)],
+            content_hash: None,
+            dirty_tiles: vec![0; dirty_words(width, height)],
         }
     }
 
-    // Convert image to Rgb565 byte array.
+    // Wrap Rgb565 data without conversion.
+    pub fn from_raw_rgb565(width: u32, height: u32, data: Vec<u8>) -> Result<Self, Error> {
+        if data.len() != ColorDepth::Bits16.buff_len(width, height) {
+            return Err(Error::DisplayError);
+        }
+        Ok(Self {
+            width,
+            height,
+            depth: ColorDepth::Bits16,
+            content_hash: None,
+            img_buff: data,
+            dirty_tiles: vec![0; dirty_words(width, height)],
+        })
+    }
+
+    // Copy Rgb565 data without conversion.
+    pub fn copy_from_rgb565_slice(&mut self, data: &[u8]) -> Result<(), Error> {
+        if self.depth != ColorDepth::Bits16 || data.len() != self.img_buff.len() {
+            return Err(Error::DisplayError);
+        }
+        self.img_buff.copy_from_slice(data);
+        self.mark_all_dirty();
+        Ok(())
+    }
+
+    // Raw buffer in the color depth of self (row-major, big endian for Rgb565).
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.img_buff
+    }
+
+    // Raw buffer for external writers. The length is fixed by the size and depth.
+    // The whole image is marked dirty, as the writes can't be tracked.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        self.mark_all_dirty();
+        &mut self.img_buff
+    }
+
+    // Write a Rgb565 pixel. Converted if the depth of self is not 16 bits.
+    // Out of bounds panics in debug builds and is clamped to the edge in release builds.
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: u16) {
+        let (x, y) = self.clamp_xy(x, y);
+        if self.depth == ColorDepth::Bits16 {
+            let k = ((y * self.width + x) * 2) as usize;
+            self.img_buff[k..k + 2].copy_from_slice(&color.to_be_bytes());
+            self.mark_dirty(x, y, 1, 1);
+        } else {
+            let (r, g, b) = rgb565_to_rgb(color);
+            self.put_rgb(x, y, r, g, b);
+        }
+    }
+
+    // Fill rectangle with a Rgb565 color directly in the buffer (clipped by self).
+    pub fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: u16) {
+        let x1 = cmp::min(x.saturating_add(w), self.width);
+        let y1 = cmp::min(y.saturating_add(h), self.height);
+        if x >= x1 || y >= y1 {
+            return;
+        }
+        self.mark_dirty(x, y, x1 - x, y1 - y);
+        if self.depth == ColorDepth::Bits16 {
+            let bytes = color.to_be_bytes();
+            for i in y..y1 {
+                let row = ((i * self.width) * 2) as usize;
+                let span = &mut self.img_buff[row + x as usize * 2..row + x1 as usize * 2];
+                for p in span.chunks_exact_mut(2) {
+                    p.copy_from_slice(&bytes);
+                }
+            }
+        } else {
+            let (r, g, b) = rgb565_to_rgb(color);
+            for i in y..y1 {
+                for j in x..x1 {
+                    self.put_rgb(j, i, r, g, b);
+                }
+            }
+        }
+    }
+
+    // Copy the rectangle of src to (dst_x, dst_y), clipped by both images.
+    // Bytes are copied by row if the depths are the same (except 12 bits), otherwise converted.
+    #[allow(clippy::too_many_arguments)]
+    pub fn blit(
+        &mut self,
+        src: &St7789Img,
+        src_x: u32,
+        src_y: u32,
+        src_w: u32,
+        src_h: u32,
+        dst_x: u32,
+        dst_y: u32,
+    ) {
+        let w = cmp::min(src_w, src.width.saturating_sub(src_x));
+        let w = cmp::min(w, self.width.saturating_sub(dst_x));
+        let h = cmp::min(src_h, src.height.saturating_sub(src_y));
+        let h = cmp::min(h, self.height.saturating_sub(dst_y));
+        self.mark_dirty(dst_x, dst_y, w, h);
+        if src.depth == self.depth && self.depth != ColorDepth::Bits12 {
+            let bpp = (self.depth.bits_per_pixel() / 8) as usize;
+            let len = w as usize * bpp;
+            for i in 0..h {
+                let s = (((src_y + i) * src.width + src_x) as usize) * bpp;
+                let d = (((dst_y + i) * self.width + dst_x) as usize) * bpp;
+                self.img_buff[d..d + len].copy_from_slice(&src.img_buff[s..s + len]);
+            }
+        } else {
+            for i in 0..h {
+                for j in 0..w {
+                    let (r, g, b) = src.get_rgb(src_x + j, src_y + i);
+                    self.put_rgb(dst_x + j, dst_y + i, r, g, b);
+                }
+            }
+        }
+    }
+
+    // Read a pixel as Rgb565. Out of bounds is handled like set_pixel.
+    pub fn get_pixel(&self, x: u32, y: u32) -> u16 {
+        let (x, y) = self.clamp_xy(x, y);
+        if self.depth == ColorDepth::Bits16 {
+            let k = ((y * self.width + x) * 2) as usize;
+            u16::from_be_bytes([self.img_buff[k], self.img_buff[k + 1]])
+        } else {
+            let (r, g, b) = self.get_rgb(x, y);
+            ((r as u16 & 0xf8) << 8) | ((g as u16 & 0xfc) << 3) | (b as u16 >> 3)
+        }
+    }
+
+    fn clamp_xy(&self, x: u32, y: u32) -> (u32, u32) {
+        debug_assert!(
+            x < self.width && y < self.height,
+            "pixel ({x}, {y}) out of {}x{}",
+            self.width,
+            self.height
+        );
+        (x.min(self.width - 1), y.min(self.height - 1))
+    }
+
+    pub fn get_depth(&self) -> ColorDepth {
+        self.depth
+    }
+
+    // Convert image to byte array in the color depth of self.
     pub fn set_image(&mut self, image: &mut RgbaImage) {
+        self.set_image_depth(image, self.depth);
+    }
+
+    // Convert image to byte array in the specified color depth.
+    pub fn set_image_depth(&mut self, image: &RgbaImage, depth: ColorDepth) {
+        if depth != self.depth {
+            self.depth = depth;
+            self.img_buff = vec![0; depth.buff_len(self.width, self.height)];
+        }
+        match depth {
+            ColorDepth::Bits12 => self.set_image_rgb444(image),
+            ColorDepth::Bits16 => self.set_image_rgb565(image),
+            ColorDepth::Bits18 => self.set_image_rgb666(image),
+        }
+        self.mark_all_dirty();
+    }
+
+    // Convert only src into the buffer at (dst_x, dst_y) ignoring alpha-channel.
+    // The part of src outside of self is skipped.
+    pub fn set_region(&mut self, src: &RgbaImage, dst_x: u32, dst_y: u32) {
+        let w = cmp::min(src.width(), self.width.saturating_sub(dst_x));
+        let h = cmp::min(src.height(), self.height.saturating_sub(dst_y));
+        for i in 0..h {
+            for j in 0..w {
+                let p = src.get_pixel(j, i);
+                self.put_rgb(dst_x + j, dst_y + i, p[0], p[1], p[2]);
+            }
+        }
+    }
+
+    // Draw update on a copy of current and set it, unless current has the same
+    // CRC32 as at the last call and the buffer has not been written since.
+    // false means the same content is already set (no need to transfer).
+    pub fn compare_and_update<F: Fn(&mut RgbaImage)>(
+        &mut self,
+        current: &RgbaImage,
+        update: F,
+    ) -> bool {
+        let hash = crc32fast::hash(current.as_raw());
+        if self.content_hash == Some(hash) {
+            return false;
+        }
+        let mut img = current.clone();
+        update(&mut img);
+        self.set_image_depth(&img, self.depth);
+        self.content_hash = Some(hash);
+        true
+    }
+
+    // Convert image compositing each pixel over bg by its alpha.
+    pub fn set_image_with_alpha_bg(&mut self, image: &RgbaImage, bg: Color) {
+        let w = cmp::min(image.width(), self.width);
+        let h = cmp::min(image.height(), self.height);
+        for i in 0..h {
+            for j in 0..w {
+                let p = image.get_pixel(j, i);
+                let a = p[3] as u16;
+                let mix = |c: u8, b: u8| ((a * c as u16 + (255 - a) * b as u16) / 255) as u8;
+                self.put_rgb(j, i, mix(p[0], bg[0]), mix(p[1], bg[1]), mix(p[2], bg[2]));
+            }
+        }
+    }
+
+    // Convert image to Rgb565 byte array.
+    fn set_image_rgb565(&mut self, image: &RgbaImage) {
         // Good to check equality of size between image and self
         // but omit for performance.
 
         // Convert Rgba to Rgb565 ignoring alpha-channel
-        //        let data: &mut [u8] = &mut self.img_buff;
         let mut k = 0;
         for i in 0..self.height {
             for j in 0..self.width {
-                let p = image.get_pixel_mut(j, i);
+                let p = image.get_pixel(j, i);
                 self.img_buff[k] = (p[0] & 0xf8u8) | ((p[1] >> 5) & 0x07u8);
                 k += 1;
                 self.img_buff[k] = ((p[1] << 3) & 0xe0u8) | ((p[2] >> 3) & 0x1fu8);
@@ -143,12 +569,474 @@ impl St7789Img {
             }
         }
     }
+
+    // Convert image to Rgb666 byte array (upper 6bits of each byte).
+    fn set_image_rgb666(&mut self, image: &RgbaImage) {
+        let mut k = 0;
+        for i in 0..self.height {
+            for j in 0..self.width {
+                let p = image.get_pixel(j, i);
+                self.img_buff[k] = p[0] & 0xfcu8;
+                self.img_buff[k + 1] = p[1] & 0xfcu8;
+                self.img_buff[k + 2] = p[2] & 0xfcu8;
+                k += 3;
+            }
+        }
+    }
+
+    // Convert image to Rgb444 byte array (2 pixels in 3 bytes).
+    fn set_image_rgb444(&mut self, image: &RgbaImage) {
+        let mut nibbles = Vec::with_capacity(6);
+        let mut k = 0;
+        for i in 0..self.height {
+            for j in 0..self.width {
+                let p = image.get_pixel(j, i);
+                nibbles.extend_from_slice(&[p[0] >> 4, p[1] >> 4, p[2] >> 4]);
+                if nibbles.len() == 6 {
+                    self.img_buff[k] = (nibbles[0] << 4) | nibbles[1];
+                    self.img_buff[k + 1] = (nibbles[2] << 4) | nibbles[3];
+                    self.img_buff[k + 2] = (nibbles[4] << 4) | nibbles[5];
+                    k += 3;
+                    nibbles.clear();
+                }
+            }
+        }
+        // Odd number of pixels
+        if !nibbles.is_empty() {
+            self.img_buff[k] = (nibbles[0] << 4) | nibbles[1];
+            self.img_buff[k + 1] = nibbles[2] << 4;
+        }
+    }
+
+    // Write a pixel in the color depth of self.
+    fn put_rgb(&mut self, x: u32, y: u32, r: u8, g: u8, b: u8) {
+        self.mark_tile(x / DIRTY_TILE, y / DIRTY_TILE);
+        let n = (y * self.width + x) as usize;
+        match self.depth {
+            ColorDepth::Bits12 => {
+                let (r, g, b) = (r >> 4, g >> 4, b >> 4);
+                let k = n * 3 / 2;
+                if n.is_multiple_of(2) {
+                    self.img_buff[k] = (r << 4) | g;
+                    self.img_buff[k + 1] = (b << 4) | (self.img_buff[k + 1] & 0x0fu8);
+                } else {
+                    self.img_buff[k] = (self.img_buff[k] & 0xf0u8) | r;
+                    self.img_buff[k + 1] = (g << 4) | b;
+                }
+            }
+            ColorDepth::Bits16 => {
+                let k = n * 2;
+                self.img_buff[k] = (r & 0xf8u8) | ((g >> 5) & 0x07u8);
+                self.img_buff[k + 1] = ((g << 3) & 0xe0u8) | ((b >> 3) & 0x1fu8);
+            }
+            ColorDepth::Bits18 => {
+                let k = n * 3;
+                self.img_buff[k] = r & 0xfcu8;
+                self.img_buff[k + 1] = g & 0xfcu8;
+                self.img_buff[k + 2] = b & 0xfcu8;
+            }
+        }
+    }
+
+    // Scale brightness of image in place. factor is in [0.0, 1.0].
+    // Used for dimming when PWM backlight is not available.
+    pub fn apply_brightness_inplace(&mut self, factor: f32) {
+        if factor >= 1.0 {
+            return;
+        }
+        let factor = factor.max(0.0);
+        let scale = |v: u8| (v as f32 * factor) as u8;
+        self.mark_all_dirty();
+
+        match self.depth {
+            ColorDepth::Bits12 => {
+                // 2 channels (nibbles) in a byte
+                let lut: Vec<u8> = (0..=255u8)
+                    .map(|b| (scale(b >> 4) << 4) | scale(b & 0x0fu8))
+                    .collect();
+                for b in self.img_buff.iter_mut() {
+                    *b = lut[*b as usize];
+                }
+            }
+            ColorDepth::Bits16 => {
+                let lut5: Vec<u16> = (0..32u8).map(|v| scale(v) as u16).collect();
+                let lut6: Vec<u16> = (0..64u8).map(|v| scale(v) as u16).collect();
+                for p in self.img_buff.chunks_exact_mut(2) {
+                    let v = u16::from_be_bytes([p[0], p[1]]);
+                    let v = (lut5[(v >> 11) as usize] << 11)
+                        | (lut6[((v >> 5) & 0x3f) as usize] << 5)
+                        | lut5[(v & 0x1f) as usize];
+                    p.copy_from_slice(&v.to_be_bytes());
+                }
+            }
+            ColorDepth::Bits18 => {
+                // upper 6bits of each byte
+                let lut: Vec<u8> = (0..=255u8).map(|b| scale(b >> 2) << 2).collect();
+                for b in self.img_buff.iter_mut() {
+                    *b = lut[*b as usize];
+                }
+            }
+        }
+    }
+
+    // Mirror image left to right in place (for mounts MADCTL can't handle).
+    pub fn apply_horizontal_flip_inplace(&mut self) {
+        let (w, h) = (self.width, self.height);
+        self.mark_all_dirty();
+        if self.depth == ColorDepth::Bits12 {
+            // 2 pixels share a byte
+            for y in 0..h {
+                for x in 0..w / 2 {
+                    let (r0, g0, b0) = self.get_rgb(x, y);
+                    let (r1, g1, b1) = self.get_rgb(w - 1 - x, y);
+                    self.put_rgb(x, y, r1, g1, b1);
+                    self.put_rgb(w - 1 - x, y, r0, g0, b0);
+                }
+            }
+            return;
+        }
+        let px = (self.depth.bits_per_pixel() / 8) as usize;
+        let w = w as usize;
+        for row in self.img_buff.chunks_exact_mut(w * px) {
+            for x in 0..w / 2 {
+                for k in 0..px {
+                    row.swap(x * px + k, (w - 1 - x) * px + k);
+                }
+            }
+        }
+    }
+
+    // Mirror image top to bottom in place.
+    pub fn apply_vertical_flip_inplace(&mut self) {
+        let (w, h) = (self.width, self.height);
+        self.mark_all_dirty();
+        let row_bits = w * self.depth.bits_per_pixel();
+        if !row_bits.is_multiple_of(8) {
+            // Odd width in 12bit: rows are not byte aligned
+            for y in 0..h / 2 {
+                for x in 0..w {
+                    let (r0, g0, b0) = self.get_rgb(x, y);
+                    let (r1, g1, b1) = self.get_rgb(x, h - 1 - y);
+                    self.put_rgb(x, y, r1, g1, b1);
+                    self.put_rgb(x, h - 1 - y, r0, g0, b0);
+                }
+            }
+            return;
+        }
+        let row_bytes = (row_bits / 8) as usize;
+        for y in 0..(h / 2) as usize {
+            let (top, bottom) = self.img_buff.split_at_mut((h as usize - 1 - y) * row_bytes);
+            top[y * row_bytes..(y + 1) * row_bytes].swap_with_slice(&mut bottom[..row_bytes]);
+        }
+    }
+
+    // Read a pixel in the color depth of self.
+    fn get_rgb(&self, x: u32, y: u32) -> (u8, u8, u8) {
+        let n = (y * self.width + x) as usize;
+        match self.depth {
+            ColorDepth::Bits12 => {
+                let k = n * 3 / 2;
+                let (r, g, b) = if n.is_multiple_of(2) {
+                    (
+                        self.img_buff[k] >> 4,
+                        self.img_buff[k] & 0x0fu8,
+                        self.img_buff[k + 1] >> 4,
+                    )
+                } else {
+                    (
+                        self.img_buff[k] & 0x0fu8,
+                        self.img_buff[k + 1] >> 4,
+                        self.img_buff[k + 1] & 0x0fu8,
+                    )
+                };
+                (r << 4, g << 4, b << 4)
+            }
+            ColorDepth::Bits16 => {
+                let k = n * 2;
+                let (hi, lo) = (self.img_buff[k], self.img_buff[k + 1]);
+                (
+                    hi & 0xf8u8,
+                    ((hi & 0x07u8) << 5) | ((lo & 0xe0u8) >> 3),
+                    (lo & 0x1fu8) << 3,
+                )
+            }
+            ColorDepth::Bits18 => {
+                let k = n * 3;
+                (self.img_buff[k], self.img_buff[k + 1], self.img_buff[k + 2])
+            }
+        }
+    }
+
+    // Decode buffer to image (alpha 255), e.g. to save what is on screen.
+    // Low bits lost in the color depth are 0.
+    pub fn to_rgba_image(&self) -> RgbaImage {
+        RgbaImage::from_fn(self.width, self.height, |x, y| {
+            let (r, g, b) = self.get_rgb(x, y);
+            Rgba([r, g, b, 0xffu8])
+        })
+    }
+
+    // Encode to PNG in memory.
+    pub fn to_png_bytes(&self) -> Result<Vec<u8>, image::ImageError> {
+        let mut cursor = Cursor::new(Vec::new());
+        PngEncoder::new(&mut cursor).encode(
+            self.to_rgba_image().as_raw(),
+            self.width,
+            self.height,
+            ColorType::Rgba8,
+        )?;
+        Ok(cursor.into_inner())
+    }
+
+    // Encode to JPEG in memory. quality is 1-100.
+    pub fn to_jpeg_bytes(&self, quality: u8) -> Result<Vec<u8>, image::ImageError> {
+        let rgb = DynamicImage::ImageRgba8(self.to_rgba_image()).to_rgb8();
+        let mut buf = Vec::new();
+        JpegEncoder::new_with_quality(&mut buf, quality).encode(
+            rgb.as_raw(),
+            self.width,
+            self.height,
+            ColorType::Rgb8,
+        )?;
+        Ok(buf)
+    }
+
+    // Scale the subregion to the size of dst (Triangle filter) and write it into dst.
+    pub fn scale_subregion_into(
+        &self,
+        src_x: u32,
+        src_y: u32,
+        src_w: u32,
+        src_h: u32,
+        dst: &mut St7789Img,
+    ) -> Result<(), Error> {
+        let in_x = src_x.checked_add(src_w).is_some_and(|x1| x1 <= self.width);
+        let in_y = src_y.checked_add(src_h).is_some_and(|y1| y1 <= self.height);
+        if src_w == 0 || src_h == 0 || !in_x || !in_y {
+            return Err(Error::DisplayError);
+        }
+        let sub = RgbaImage::from_fn(src_w, src_h, |x, y| {
+            let (r, g, b) = self.get_rgb(src_x + x, src_y + y);
+            Rgba([r, g, b, 0xffu8])
+        });
+        let scaled = imageops::resize(&sub, dst.width, dst.height, FilterType::Triangle);
+        dst.set_image_depth(&scaled, dst.depth);
+        Ok(())
+    }
+
+    // Blur image in place.
+    pub fn apply_blur_inplace(&mut self, radius: u32) {
+        let blurred = imageops::blur(&self.to_rgba_image(), radius as f32);
+        self.set_image_depth(&blurred, self.depth);
+    }
+
+    // Overlay image. Only pixels with alpha over half are written.
+    pub fn overlay_image(&mut self, image: &RgbaImage) {
+        let w = cmp::min(self.width, image.width());
+        let h = cmp::min(self.height, image.height());
+        for i in 0..h {
+            for j in 0..w {
+                let p = image.get_pixel(j, i);
+                if p[3] >= 0x80u8 {
+                    self.put_rgb(j, i, p[0], p[1], p[2]);
+                }
+            }
+        }
+    }
+
+    // Mark tiles covering the rectangle as dirty (clipped by self).
+    fn mark_dirty(&mut self, x: u32, y: u32, w: u32, h: u32) {
+        let x1 = cmp::min(x.saturating_add(w), self.width);
+        let y1 = cmp::min(y.saturating_add(h), self.height);
+        if x >= x1 || y >= y1 {
+            return;
+        }
+        for ty in y / DIRTY_TILE..=(y1 - 1) / DIRTY_TILE {
+            for tx in x / DIRTY_TILE..=(x1 - 1) / DIRTY_TILE {
+                self.mark_tile(tx, ty);
+            }
+        }
+    }
+
+    fn mark_tile(&mut self, tx: u32, ty: u32) {
+        let n = (ty * self.width.div_ceil(DIRTY_TILE) + tx) as usize;
+        self.dirty_tiles[n / 64] |= 1u64 << (n % 64);
+        self.content_hash = None;
+    }
+
+    fn is_tile_dirty(&self, tx: u32, ty: u32) -> bool {
+        let n = (ty * self.width.div_ceil(DIRTY_TILE) + tx) as usize;
+        self.dirty_tiles[n / 64] & (1u64 << (n % 64)) != 0
+    }
+
+    // Mark the whole image as dirty (after writes not tracked by pixel).
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty_tiles.fill(u64::MAX);
+        self.content_hash = None;
+    }
+
+    pub fn clear_dirty(&mut self) {
+        self.dirty_tiles.fill(0);
+    }
+
+    // Rectangles (x0, y0, x1, y1) (inclusive) written since the last clear_dirty.
+    // Dirty tiles next to each other in a tile row are merged into one rectangle.
+    pub fn dirty_regions(&self) -> impl Iterator<Item = (u16, u16, u16, u16)> + '_ {
+        let (tiles_x, tiles_y) = (
+            self.width.div_ceil(DIRTY_TILE),
+            self.height.div_ceil(DIRTY_TILE),
+        );
+        (0..tiles_y).flat_map(move |ty| {
+            let y0 = ty * DIRTY_TILE;
+            let y1 = cmp::min(y0 + DIRTY_TILE, self.height) - 1;
+            let mut runs = Vec::new();
+            let mut start = None;
+            for tx in 0..=tiles_x {
+                match (start, tx < tiles_x && self.is_tile_dirty(tx, ty)) {
+                    (None, true) => start = Some(tx),
+                    (Some(s), false) => {
+                        let x1 = cmp::min(tx * DIRTY_TILE, self.width) - 1;
+                        runs.push(((s * DIRTY_TILE) as u16, y0 as u16, x1 as u16, y1 as u16));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+            runs
+        })
+    }
+}
+
+// Size in u64 words of the dirty tile bitmask.
+fn dirty_words(width: u32, height: u32) -> usize {
+    let tiles = width.div_ceil(DIRTY_TILE) * height.div_ceil(DIRTY_TILE);
+    tiles.div_ceil(64) as usize
+}
+
+// Expand Rgb565 to 8 bits per channel (low bits filled with high bits).
+fn rgb565_to_rgb(color: u16) -> (u8, u8, u8) {
+    let r = ((color >> 11) as u8) << 3;
+    let g = (((color >> 5) & 0x3f) as u8) << 2;
+    let b = ((color & 0x1f) as u8) << 3;
+    (r | (r >> 5), g | (g >> 6), b | (b >> 5))
+}
+
+// Darken image to 50% in place.
+// Each color channel is halved without carrying bits into the next channel.
+pub fn grey_image_in_place(img: &mut St7789Img) {
+    img.mark_all_dirty();
+    match img.depth {
+        ColorDepth::Bits12 => {
+            for b in img.img_buff.iter_mut() {
+                *b = (*b >> 1) & 0x77u8;
+            }
+        }
+        ColorDepth::Bits16 => {
+            for p in img.img_buff.chunks_exact_mut(2) {
+                let v = (u16::from_be_bytes([p[0], p[1]]) >> 1) & 0x7befu16;
+                p.copy_from_slice(&v.to_be_bytes());
+            }
+        }
+        ColorDepth::Bits18 => {
+            for b in img.img_buff.iter_mut() {
+                *b = (*b >> 1) & 0xfcu8;
+            }
+        }
+    }
+}
+
+impl Default for St7789Builder {
+    fn default() -> Self {
+        Self {
+            pin_rst: None,
+            pin_backlight: None,
+            pin_te: None,
+            width: 240,
+            height: 240,
+            rotation: ROTATION::Rot0,
+            inverted: true,
+        }
+    }
+}
+
+impl St7789Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_reset_pin(mut self, pin: OutputPin) -> Self {
+        self.pin_rst = Some(pin);
+        self
+    }
+
+    pub fn with_backlight_pin(mut self, pin: OutputPin) -> Self {
+        self.pin_backlight = Some(pin);
+        self
+    }
+
+    // TE pin to wait for before writing an image, if tearing effect is enabled.
+    pub fn with_te_pin(mut self, pin: InputPin) -> Self {
+        self.pin_te = Some(pin);
+        self
+    }
+
+    pub fn with_dimensions(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: ROTATION) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    // Most IPS panels need inversion on, TN panels off.
+    pub fn with_inversion(mut self, inverted: bool) -> Self {
+        self.inverted = inverted;
+        self
+    }
+
+    // Make St7789 on di. Error if the rotation is not available for the size.
+    pub fn build<DI: WriteOnlyDataCommand>(self, di: DI) -> Result<St7789<DI>, Error> {
+        let (x_offset, y_offset) =
+            St7789::<DI>::calc_offset(self.width, self.height, self.rotation)
+                .ok_or(Error::DisplayError)?;
+        let (width, height) = (self.width, self.height);
+        Ok(St7789 {
+            di,
+            pin_rst: self.pin_rst,
+            pin_backlight: self.pin_backlight,
+            pin_te: self.pin_te,
+            tearing_mode: None,
+            width,
+            height,
+            rotation: self.rotation,
+            inverted: self.inverted,
+            color_depth: ColorDepth::Bits16,
+            addressing_mode: AddressingMode::Bit16,
+            lcm_ctrl: None,
+            gctrl: None,
+            scroll_area: (0, FRAME_LINES, 0),
+            vrhs: None,
+            vdvs: None,
+            gamma: None,
+            x_trim: 0,
+            y_trim: 0,
+            x0: x_offset,
+            y0: y_offset,
+            x1: width as u16 + x_offset - 1u16,
+            y1: height as u16 + y_offset - 1u16,
+            on_display_error: None,
+        })
+    }
 }
 
 impl<DI> St7789<DI>
 where
     DI: WriteOnlyDataCommand,
 {
+    // Same as St7789Builder, but panics on unsupported rotation.
     pub fn new(
         di: DI,
         pin_rst: Option<OutputPin>,
@@ -157,6 +1045,28 @@ where
         height: u32,
         rotation: ROTATION,
     ) -> Self {
+        St7789Builder {
+            pin_rst,
+            pin_backlight,
+            pin_te: None,
+            width,
+            height,
+            rotation,
+            inverted: true,
+        }
+        .build(di)
+        .expect("Unsupported rotation")
+    }
+
+    // Set the callback for errors of display_img (None to remove).
+    // Extension point for monitoring outside the driver.
+    pub fn set_on_display_error(&mut self, callback: Option<ErrorCallback>) {
+        self.on_display_error = callback.map(OnDisplayError);
+    }
+
+    // Calc offsets of the display window in the 240x320 frame memory.
+    // None if rotation is not available for the display size.
+    fn calc_offset(width: u32, height: u32, rotation: ROTATION) -> Option<(u16, u16)> {
         let mut x_offset = 0u16;
         let mut y_offset = 0u16;
         let mut row_offset = 0u16;
@@ -173,7 +1083,7 @@ where
                 x_offset = row_offset;
                 y_offset = col_offset;
                 if width != height {
-                    panic!();
+                    return None;
                 }
             }
             ROTATION::Rot180 => {
@@ -182,22 +1092,11 @@ where
             }
             ROTATION::Rot270 => {
                 if width != height {
-                    panic!();
+                    return None;
                 }
             }
         }
-        Self {
-            di,
-            pin_rst,
-            pin_backlight,
-            width,
-            height,
-            rotation,
-            x0: x_offset,
-            y0: y_offset,
-            x1: width as u16 + x_offset - 1u16,
-            y1: height as u16 + y_offset - 1u16,
-        }
+        Some((x_offset, y_offset))
     }
 
     pub fn get_width(&self) -> u32 {
@@ -207,6 +1106,10 @@ where
         }
     }
 
+    pub fn get_rotation(&self) -> ROTATION {
+        self.rotation
+    }
+
     pub fn get_height(&self) -> u32 {
         match self.rotation {
             ROTATION::Rot90 | ROTATION::Rot270 => self.height,
@@ -220,6 +1123,27 @@ where
             .map_err(|_| Error::DisplayError)
     }
 
+    // No operation. Keeps the bus active where the datasheet requires no
+    // delay but some activity between command groups (e.g. after SLPIN).
+    pub fn nop(&mut self) -> Result<(), Error> {
+        self.send_command(ST7789_NOP)
+    }
+
+    // Send count NOPs to pad timing.
+    pub fn nop_delay(&mut self, count: u32) -> Result<(), Error> {
+        for _ in 0..count {
+            self.nop()?;
+        }
+        Ok(())
+    }
+
+    // Send command and its parameters at once.
+    pub fn send_command_data(&mut self, command: u8, data: &[u8]) -> Result<(), Error> {
+        self.di
+            .write_command_data_batched(command, data)
+            .map_err(|_| Error::DisplayError)
+    }
+
     pub fn send_data(&mut self, data: &[u8]) -> Result<(), Error> {
  	// It's better to check or loop for data size over CHUNK_SIZE here...
  	// But for performance, you have to do them in caller.
@@ -253,16 +1177,34 @@ where
         thread::sleep(Duration::from_millis(200));
         self.send_command(ST7789_SLPOUT)?; // turn off sleep
         thread::sleep(Duration::from_millis(200));
-        self.send_command(ST7789_VSCRDER)?; // vertical scroll definition
-        self.send_data(&[0x00u8, 0x00u8, 0x14u8, 0x00u8, 0x00u8, 0x00u8])?; // 0 TSA, 320 VSA, 0 BSA
+        // vertical scroll definition (0 TSA, 320 VSA, 0 BSA by default)
+        let (top_fixed, scroll_area, bottom_fixed) = self.scroll_area;
+        self.set_scroll_area(top_fixed, scroll_area, bottom_fixed)?;
         self.send_command(ST7789_NORON)?; // turn on display
         thread::sleep(Duration::from_millis(10));
-        self.send_command(ST7789_INVON)?; // back?
+        self.set_inversion(self.inverted)?;
 
         self.set_rotation(self.rotation)?;
 
-        self.send_command(ST7789_COLMOD)?; // 16bit 65k color
-        self.send_data(&[0x55u8])?;
+        self.set_color_mode(self.color_depth)?;
+        if let Some(value) = self.lcm_ctrl {
+            self.set_lcm_control(value)?;
+        }
+        if let Some(value) = self.gctrl {
+            self.set_gate_control((value >> 4) & 0x07, value & 0x07)?;
+        }
+        if let Some(value) = self.vrhs {
+            self.set_vrhs(value)?;
+        }
+        if let Some(value) = self.vdvs {
+            self.set_vdvs(value)?;
+        }
+        if let Some(gamma) = self.gamma {
+            self.set_gamma(gamma)?;
+        }
+        if let Some(mode) = self.tearing_mode {
+            self.enable_tearing_effect(mode)?;
+        }
         self.send_command(ST7789_DISPON)?; // turn on display
         thread::sleep(Duration::from_millis(200));
 
@@ -282,32 +1224,319 @@ where
         Ok(())
     }
 
+    // Blank display and sleep in. Frame memory is kept. Backlight is not changed.
+    pub fn sleep(&mut self) -> Result<(), Error> {
+        self.send_command(ST7789_DISPOFF)?;
+        self.send_command(ST7789_SLPIN)?;
+        thread::sleep(Duration::from_millis(5));
+        Ok(())
+    }
+
+    // Sleep out and show frame memory again.
+    pub fn wake(&mut self) -> Result<(), Error> {
+        self.send_command(ST7789_SLPOUT)?;
+        // Power supply and clock settle (120msec before next SLPIN)
+        thread::sleep(Duration::from_millis(120));
+        self.send_command(ST7789_DISPON)?;
+        Ok(())
+    }
+
+    // Blank display, turn off backlight and sleep in. init wakes it up again.
+    pub fn shutdown(&mut self) -> Result<(), Error> {
+        self.send_command(ST7789_DISPOFF)?;
+        self.set_backlight(false)?;
+        self.send_command(ST7789_SLPIN)?;
+        thread::sleep(Duration::from_millis(5));
+        Ok(())
+    }
+
+    // Set display inversion (INVON/INVOFF), kept over init.
+    pub fn set_inversion(&mut self, inverted: bool) -> Result<(), Error> {
+        self.send_command(if inverted {
+            ST7789_INVON
+        } else {
+            ST7789_INVOFF
+        })?;
+        self.inverted = inverted;
+        Ok(())
+    }
+
+    // Set color depth of the interface.
+    // Images to display must be in the same depth.
+    pub fn set_color_mode(&mut self, depth: ColorDepth) -> Result<(), Error> {
+        self.send_command_data(ST7789_COLMOD, &[depth.colmod()])?;
+        self.color_depth = depth;
+        Ok(())
+    }
+
+    // Set LCM control (bits XORed with MADCTL and INVON), kept over init.
+    // bit 6: XMY, 5: XBGR, 4: XINV, 3: XMX, 2: XMH, 1: XMV, 0: XGS. Bit 7 must be 0.
+    // Tested values: 0x2C for standard IPS panels (power-on default).
+    pub fn set_lcm_control(&mut self, value: u8) -> Result<(), Error> {
+        self.send_command_data(ST7789_LCMCTRL, &[value])?;
+        self.lcm_ctrl = Some(value);
+        Ok(())
+    }
+
+    // Set gate control (VGH/VGL), kept over init. Adjust it if the panel ghosts.
+    // bit 6-4: VGH 0-7 (12.2V-14.97V), bit 2-0: VGL 0-7 (-7.16V to -12.5V). Others are 0.
+    // Power-on default is 0x35 (VGH 13.26V, VGL -10.43V).
+    pub fn set_gate_control(&mut self, vgh: u8, vgl: u8) -> Result<(), Error> {
+        if vgh > 7 || vgl > 7 {
+            return Err(Error::DisplayError);
+        }
+        let value = (vgh << 4) | vgl;
+        self.send_command_data(ST7789_GCTRL, &[value])?;
+        self.gctrl = Some(value);
+        Ok(())
+    }
+
+    // Set VRH (GVDD voltage), kept over init. 0x00-0x27.
+    // Raise it if the panel looks washed out. See VRHS_TABLE.
+    pub fn set_vrhs(&mut self, value: u8) -> Result<(), Error> {
+        // VDV and VRH are taken from the registers instead of NVM
+        self.send_command_data(ST7789_VDVVRHEN, &[0x01, 0xFF])?;
+        self.send_command_data(ST7789_VRHS, &[value & 0x3F])?;
+        self.vrhs = Some(value);
+        Ok(())
+    }
+
+    // Set VDV voltage, kept over init. 0x00-0x3F. See VDVS_TABLE.
+    pub fn set_vdvs(&mut self, value: u8) -> Result<(), Error> {
+        self.send_command_data(ST7789_VDVVRHEN, &[0x01, 0xFF])?;
+        self.send_command_data(ST7789_VDVS, &[value & 0x3F])?;
+        self.vdvs = Some(value);
+        Ok(())
+    }
+
+    // Output the TE signal in mode, kept over init.
+    // With a TE pin, images are written after the start of the vertical blank.
+    pub fn enable_tearing_effect(&mut self, mode: TearingMode) -> Result<(), Error> {
+        if let Some(pin) = self.pin_te.as_mut() {
+            pin.set_interrupt(Trigger::RisingEdge, None)
+                .map_err(|_| Error::DisplayError)?;
+        }
+        self.send_command_data(ST7789_TEON, &[mode as u8])?;
+        self.tearing_mode = Some(mode);
+        Ok(())
+    }
+
+    pub fn disable_tearing_effect(&mut self) -> Result<(), Error> {
+        self.send_command(ST7789_TEOFF)?;
+        self.tearing_mode = None;
+        if let Some(pin) = self.pin_te.as_mut() {
+            pin.clear_interrupt().map_err(|_| Error::DisplayError)?;
+        }
+        Ok(())
+    }
+
+    // Wait for the rising edge of TE (start of blanking) if enabled and the pin is given.
+    // Gives up after TE_TIMEOUT_MSEC not to stall when TE is not connected.
+    fn wait_for_te(&mut self) {
+        if self.tearing_mode.is_none() {
+            return;
+        }
+        let Some(pin) = self.pin_te.as_mut() else {
+            return;
+        };
+        // reset: skip an edge latched before now, as the remaining blank time is unknown
+        let timeout = Duration::from_millis(TE_TIMEOUT_MSEC);
+        let _ = pin.poll_interrupt(true, Some(timeout));
+    }
+
+    // Set a named gamma curve, kept over init.
+    pub fn set_gamma_curve(&mut self, curve: GammaCurve) -> Result<(), Error> {
+        self.set_gamma(curve.gamma())
+    }
+
+    // Set gamma curves, kept over init.
+    pub fn set_gamma(&mut self, gamma: Gamma) -> Result<(), Error> {
+        self.send_command_data(ST7789_GMCTRP1, &gamma.positive)?;
+        self.send_command_data(ST7789_GMCTRN1, &gamma.negative)?;
+        self.gamma = Some(gamma);
+        Ok(())
+    }
+
     // Set display rotation
     pub fn set_rotation(&mut self, rotation: ROTATION) -> Result<(), Error> {
-        self.send_command(ST7789_MADCTL)?; // reset display
-        self.send_data(&[rotation as u8])?;
+        let window = self
+            .calc_window(rotation, self.x_trim, self.y_trim)
+            .ok_or(Error::DisplayError)?;
+        self.send_command_data(ST7789_MADCTL, &[rotation as u8])?; // reset display
         self.rotation = rotation;
+        (self.x0, self.y0, self.x1, self.y1) = window;
         Ok(())
     }
 
+    // Shift the display window by x and y pixels to trim panel variations.
+    // Takes effect on the next display_img call.
+    // The window must fit in the 240x320 frame memory:
+    // x + base x offset + width <= 240, y + base y offset + height <= 320
+    // (e.g. 0 for 240x320, up to 80 in y for 240x240 with Rot0).
+    // Rot90 and Rot270 swap the frame memory to 320x240.
+    // Fails without changing the window if it does not fit.
+    pub fn set_display_offset(&mut self, x: u16, y: u16) -> Result<(), Error> {
+        let window = self
+            .calc_window(self.rotation, x, y)
+            .ok_or(Error::DisplayError)?;
+        self.x_trim = x;
+        self.y_trim = y;
+        (self.x0, self.y0, self.x1, self.y1) = window;
+        Ok(())
+    }
+
+    // Set bytes per coordinate of the window. Bit8 only for width and height < 256.
+    pub fn set_addressing_mode(&mut self, mode: AddressingMode) -> Result<(), Error> {
+        if mode == AddressingMode::Bit8 && (self.width >= 256 || self.height >= 256) {
+            return Err(Error::DisplayError);
+        }
+        self.addressing_mode = mode;
+        Ok(())
+    }
+
+    // Display window (x0, y0, x1, y1) from the base offsets of rotation and the trim.
+    // None if rotation is not available or the window does not fit in the frame memory.
+    fn calc_window(
+        &self,
+        rotation: ROTATION,
+        x_trim: u16,
+        y_trim: u16,
+    ) -> Option<(u16, u16, u16, u16)> {
+        let (x_offset, y_offset) = Self::calc_offset(self.width, self.height, rotation)?;
+        let (frame_width, frame_height) = match rotation {
+            ROTATION::Rot90 | ROTATION::Rot270 => (FRAME_LINES as u32, 240),
+            _ => (240, FRAME_LINES as u32),
+        };
+        let x0 = x_offset as u32 + x_trim as u32;
+        let y0 = y_offset as u32 + y_trim as u32;
+        if x0 + self.width > frame_width || y0 + self.height > frame_height {
+            return None;
+        }
+        Some((
+            x0 as u16,
+            y0 as u16,
+            (x0 + self.width - 1) as u16,
+            (y0 + self.height - 1) as u16,
+        ))
+    }
+
+    // Restrict active display to the rows from start_row to end_row (inclusive).
+    // Images are still sent entirely, but only the rows are updated on the panel.
+    pub fn set_partial_window(&mut self, start_row: u16, end_row: u16) -> Result<(), Error> {
+        if start_row > end_row || end_row > self.y1 - self.y0 {
+            return Err(Error::DisplayError);
+        }
+        let [s0, s1] = (self.y0 + start_row).to_be_bytes();
+        let [e0, e1] = (self.y0 + end_row).to_be_bytes();
+        self.send_command_data(ST7789_PTLAR, &[s0, s1, e0, e1])?; // Partial area
+        self.send_command(ST7789_PTLON)?; // Partial mode on
+        Ok(())
+    }
+
+    // Back to the normal display mode.
+    pub fn exit_partial_window(&mut self) -> Result<(), Error> {
+        self.send_command(ST7789_NORON)?; // Normal mode on
+        Ok(())
+    }
+
+    // Same as set_partial_window.
+    pub fn enable_partial_mode(&mut self, start_row: u16, end_row: u16) -> Result<(), Error> {
+        self.set_partial_window(start_row, end_row)
+    }
+
+    // Same as exit_partial_window.
+    pub fn disable_partial_mode(&mut self) -> Result<(), Error> {
+        self.exit_partial_window()
+    }
+
+    // Set the frame memory line shown at the top of the scroll area (VSCAD).
+    // With the default scroll area of all 320 lines, this pans the whole
+    // screen, not a single row. The line wraps around within 0-319.
+    pub fn set_vertical_scroll(&mut self, line: u16) -> Result<(), Error> {
+        self.send_command_data(ST7789_VSCAD, &(line % FRAME_LINES).to_be_bytes())
+    }
+
+    // Define the vertical scroll area (VSCRDER) in frame memory lines, kept over init.
+    // The three must add up to 320. Lines in the fixed areas do not scroll.
+    pub fn set_scroll_area(
+        &mut self,
+        top_fixed: u16,
+        scroll_area: u16,
+        bottom_fixed: u16,
+    ) -> Result<(), Error> {
+        if scroll_area == 0
+            || top_fixed as u32 + scroll_area as u32 + bottom_fixed as u32 != FRAME_LINES as u32
+        {
+            return Err(Error::DisplayError);
+        }
+        let [t0, t1] = top_fixed.to_be_bytes();
+        let [s0, s1] = scroll_area.to_be_bytes();
+        let [b0, b1] = bottom_fixed.to_be_bytes();
+        self.send_command_data(ST7789_VSCRDER, &[t0, t1, s0, s1, b0, b1])?;
+        self.scroll_area = (top_fixed, scroll_area, bottom_fixed);
+        Ok(())
+    }
+
+    // Scroll the scroll area by offset lines (wraps around within the area).
+    // Only VSCAD is sent, no pixel data.
+    pub fn set_scroll_offset(&mut self, offset: u16) -> Result<(), Error> {
+        let (top_fixed, scroll_area, _) = self.scroll_area;
+        self.set_vertical_scroll(top_fixed + offset % scroll_area)
+    }
+
     // Set the pixel address window for proceeding drawing commands.
     // x0 and x1 should define the minimum and muximum x pixel bounds.
     // y0 and y1 should define the minimum and maximum y pixel bounds.
     pub fn set_window(&mut self, x0: u16, y0: u16, x1: u16, y1: u16) -> Result<(), Error> {
-        self.send_command(ST7789_CASET)?; // Column addr set
-        self.send_data(&x0.to_be_bytes())?;
-        self.send_data(&x1.to_be_bytes())?;
-        self.send_command(ST7789_RASET)?; // Row addr set
-        self.send_data(&y0.to_be_bytes())?;
-        self.send_data(&y1.to_be_bytes())?;
+        if self.addressing_mode == AddressingMode::Bit8 {
+            // Window (with offsets) must be addressable in a byte
+            let to_u8 = |v: u16| u8::try_from(v).map_err(|_| Error::DisplayError);
+            let (x0, x1, y0, y1) = (to_u8(x0)?, to_u8(x1)?, to_u8(y0)?, to_u8(y1)?);
+            self.send_command_data(ST7789_CASET, &[x0, x1])?; // Column addr set
+            self.send_command_data(ST7789_RASET, &[y0, y1])?; // Row addr set
+            return Ok(());
+        }
+        let [x0h, x0l] = x0.to_be_bytes();
+        let [x1h, x1l] = x1.to_be_bytes();
+        let [y0h, y0l] = y0.to_be_bytes();
+        let [y1h, y1l] = y1.to_be_bytes();
+        self.send_command_data(ST7789_CASET, &[x0h, x0l, x1h, x1l])?; // Column addr set
+        self.send_command_data(ST7789_RASET, &[y0h, y0l, y1h, y1l])?; // Row addr set
         Ok(())
     }
 
     // Write the provided image to the hardware
     pub fn display_img(&mut self, img: &St7789Img) -> Result<(), Error> {
+        self.display_img_with_progress(img, |_, _| ())
+    }
+
+    // Write the provided image to the hardware,
+    // calling progress_cb(bytes_sent, total_bytes) after each chunk.
+    pub fn display_img_with_progress<F: Fn(u32, u32)>(
+        &mut self,
+        img: &St7789Img,
+        progress_cb: F,
+    ) -> Result<(), Error> {
+        let result = self.write_img(img, progress_cb);
+        self.notify_error(&result);
+        result
+    }
+
+    // Pass the error of a write to the callback set by set_on_display_error.
+    fn notify_error(&self, result: &Result<(), Error>) {
+        if let (Err(e), Some(callback)) = (result, &self.on_display_error) {
+            (callback.0)(e);
+        }
+    }
+
+    fn write_img<F: Fn(u32, u32)>(&mut self, img: &St7789Img, progress_cb: F) -> Result<(), Error> {
+        if img.depth != self.color_depth {
+            return Err(Error::DisplayError);
+        }
         // Set address bounds to entire display
         self.set_window(self.x0, self.y0, self.x1, self.y1)?;
 
+        self.wait_for_te();
         self.send_command(ST7789_RAMWR)?; // Write to RAM
                                           // Write data to H/W
         let mut i = 0;
@@ -318,7 +1547,413 @@ where
             let slice = &img.img_buff[i..end];
             self.send_data(slice)?;
             i = end;
+            progress_cb(i as u32, n as u32);
+        }
+        Ok(())
+    }
+
+    // Write only the rows from start_row to end_row (inclusive) of the provided image.
+    // Efficient for full-width bands like the seek bar or the spectrum.
+    pub fn display_img_rows(
+        &mut self,
+        img: &St7789Img,
+        start_row: u16,
+        end_row: u16,
+    ) -> Result<(), Error> {
+        let row_bits = img.width * img.depth.bits_per_pixel();
+        if img.depth != self.color_depth
+            || start_row > end_row
+            || end_row as u32 >= img.height
+            || !row_bits.is_multiple_of(8)
+        {
+            return Err(Error::DisplayError);
+        }
+        self.set_window(self.x0, self.y0 + start_row, self.x1, self.y0 + end_row)?;
+
+        self.send_command(ST7789_RAMWR)?; // Write to RAM
+
+        // img_buff is row-major, so the rows are consecutive bytes.
+        let row_len = (row_bits / 8) as usize;
+        let rows = &img.img_buff[start_row as usize * row_len..(end_row as usize + 1) * row_len];
+        for slice in rows.chunks(CHUNK_SIZE as usize) {
+            self.send_data(slice)?;
+        }
+        Ok(())
+    }
+
+    // Write only the tiles of img written since the last flush, then clear them.
+    // Waits for TE and reports errors like display_img. Tiles stay dirty on error.
+    pub fn flush_dirty(&mut self, img: &mut St7789Img) -> Result<(), Error> {
+        let result = self.write_dirty(img);
+        self.notify_error(&result);
+        result
+    }
+
+    fn write_dirty(&mut self, img: &mut St7789Img) -> Result<(), Error> {
+        let regions: Vec<_> = img.dirty_regions().collect();
+        if regions.is_empty() {
+            return Ok(());
+        }
+        self.wait_for_te();
+        for (x0, y0, x1, y1) in regions {
+            self.display_region(img, x0, y0, x1, y1)?;
+        }
+        img.clear_dirty();
+        Ok(())
+    }
+
+    // Write only the rectangle from (x0, y0) to (x1, y1) (inclusive) of the provided image.
+    // Coordinates are in the image (without offsets). For 12 bits depth, x0 and
+    // the width must be even to start and end rows on byte boundaries.
+    pub fn display_region(
+        &mut self,
+        img: &St7789Img,
+        x0: u16,
+        y0: u16,
+        x1: u16,
+        y1: u16,
+    ) -> Result<(), Error> {
+        let bits = img.depth.bits_per_pixel();
+        let (x0_bits, w_bits) = (
+            x0 as u32 * bits,
+            (x1 as u32 + 1).saturating_sub(x0 as u32) * bits,
+        );
+        if img.depth != self.color_depth
+            || x0 > x1
+            || y0 > y1
+            || x1 as u32 >= img.width
+            || y1 as u32 >= img.height
+            || !x0_bits.is_multiple_of(8)
+            || !w_bits.is_multiple_of(8)
+            || !(img.width * bits).is_multiple_of(8)
+        {
+            return Err(Error::DisplayError);
+        }
+        self.set_window(self.x0 + x0, self.y0 + y0, self.x0 + x1, self.y0 + y1)?;
+
+        self.send_command(ST7789_RAMWR)?; // Write to RAM
+
+        // Rows of the region are not consecutive in img_buff, so batch them.
+        let row_len = (img.width * bits / 8) as usize;
+        let (start, len) = ((x0_bits / 8) as usize, (w_bits / 8) as usize);
+        let mut buf = Vec::with_capacity(CHUNK_SIZE as usize);
+        for y in y0 as usize..=y1 as usize {
+            let k = y * row_len + start;
+            for slice in img.img_buff[k..k + len].chunks(CHUNK_SIZE as usize) {
+                if buf.len() + slice.len() > CHUNK_SIZE as usize {
+                    self.send_data(&buf)?;
+                    buf.clear();
+                }
+                buf.extend_from_slice(slice);
+            }
+        }
+        if !buf.is_empty() {
+            self.send_data(&buf)?;
         }
         Ok(())
     }
+
+    // Write a row of Rgb565 pixels (one per column) at y.
+    pub fn write_pixel_row(&mut self, y: u16, pixels: &[u16]) -> Result<(), Error> {
+        self.write_scan_lines(y, &[pixels])
+    }
+
+    // Write a single Rgb565 pixel at (x, y) without a framebuffer.
+    // Slow for many pixels: the window is set for each one.
+    pub fn draw_pixel(&mut self, x: u16, y: u16, color: u16) -> Result<(), Error> {
+        if self.color_depth != ColorDepth::Bits16 || x > self.x1 - self.x0 || y > self.y1 - self.y0
+        {
+            return Err(Error::DisplayError);
+        }
+        let (x, y) = (self.x0 + x, self.y0 + y);
+        self.set_window(x, y, x, y)?;
+        self.send_command(ST7789_RAMWR)?; // Write to RAM
+        self.send_data(&color.to_be_bytes())
+    }
+
+    // Fill the whole display with a Rgb565 color without an image.
+    pub fn fill_screen(&mut self, color: u16) -> Result<(), Error> {
+        if self.color_depth != ColorDepth::Bits16 {
+            return Err(Error::DisplayError);
+        }
+        self.set_window(self.x0, self.y0, self.x1, self.y1)?;
+
+        self.send_command(ST7789_RAMWR)?; // Write to RAM
+        let mut buf = [0u8; CHUNK_SIZE as usize];
+        for pixel in buf.chunks_exact_mut(2) {
+            pixel.copy_from_slice(&color.to_be_bytes());
+        }
+        let mut remaining = self.width as usize * self.height as usize * 2;
+        while remaining > 0 {
+            let n = cmp::min(remaining, buf.len());
+            self.send_data(&buf[..n])?;
+            remaining -= n;
+        }
+        Ok(())
+    }
+
+    // Write rows of Rgb565 pixels from start_y.
+    // The window is set once and all rows are sent in sequence.
+    pub fn write_scan_lines<R: AsRef<[u16]>>(
+        &mut self,
+        start_y: u16,
+        rows: &[R],
+    ) -> Result<(), Error> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let end_y = start_y as u32 + rows.len() as u32 - 1;
+        let width = (self.x1 - self.x0 + 1) as usize;
+        if self.color_depth != ColorDepth::Bits16
+            || end_y > (self.y1 - self.y0) as u32
+            || rows.iter().any(|row| row.as_ref().len() != width)
+        {
+            return Err(Error::DisplayError);
+        }
+        self.set_window(self.x0, self.y0 + start_y, self.x1, self.y0 + end_y as u16)?;
+
+        self.send_command(ST7789_RAMWR)?; // Write to RAM
+        let mut buf = Vec::with_capacity(CHUNK_SIZE as usize);
+        for row in rows {
+            for pixels in row.as_ref().chunks(CHUNK_SIZE as usize / 2) {
+                buf.clear();
+                for p in pixels {
+                    buf.extend_from_slice(&p.to_be_bytes());
+                }
+                self.send_data(&buf)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<DI> St7789<DI>
+where
+    DI: WriteOnlyDataCommand + ReadDataCommand,
+{
+    // Read parameters of a read command (e.g. RDDCOLMOD) into buf.
+    pub fn read_register(&mut self, command: u8, buf: &mut [u8]) -> Result<(), Error> {
+        self.di
+            .read_data(command, buf)
+            .map_err(|_| Error::DisplayError)
+    }
+
+    // Read back COLMOD and MADCTL set by init.
+    // Returns (command, read, expected) of each mismatch, empty if all match.
+    // Mismatches hint e.g. MISO not connected or wrong SPI mode.
+    pub fn verify_init_sequence(&mut self) -> Result<Vec<(u8, u8, u8)>, Error> {
+        let checks = [
+            (ST7789_RDDCOLMOD, self.color_depth.colmod()),
+            (ST7789_RDDMADCTL, self.rotation as u8),
+        ];
+        let mut mismatches = Vec::new();
+        let mut buf = [0u8; 1];
+        for (command, expected) in checks {
+            self.read_register(command, &mut buf)?;
+            if buf[0] != expected {
+                mismatches.push((command, buf[0], expected));
+            }
+        }
+        Ok(mismatches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::{DisplayError, MockInterface, MockOp, SPIInterfaceAutoCS};
+    use embedded_hal::{digital, spi};
+    use std::convert::Infallible;
+
+    fn mock_display() -> St7789<MockInterface> {
+        St7789Builder::new()
+            .build(MockInterface::recording())
+            .unwrap()
+    }
+
+    // SpiDevice failing every transaction, to inject bus errors.
+    struct FailingSpi;
+
+    impl spi::ErrorType for FailingSpi {
+        type Error = spi::ErrorKind;
+    }
+
+    impl spi::SpiDevice for FailingSpi {
+        fn transaction(&mut self, _: &mut [spi::Operation<'_, u8>]) -> Result<(), Self::Error> {
+            Err(spi::ErrorKind::Other)
+        }
+    }
+
+    struct DummyPin;
+
+    impl digital::ErrorType for DummyPin {
+        type Error = Infallible;
+    }
+
+    impl digital::OutputPin for DummyPin {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn set_gamma_sends_positive_then_negative() {
+        let positive: [u8; 14] = std::array::from_fn(|i| i as u8);
+        let negative: [u8; 14] = std::array::from_fn(|i| 0x80 | i as u8);
+        let mut display = mock_display();
+        display.set_gamma(Gamma { positive, negative }).unwrap();
+        assert_eq!(
+            display.di.ops(),
+            [
+                MockOp::Command(0xE0),
+                MockOp::Data(positive.to_vec()),
+                MockOp::Command(0xE1),
+                MockOp::Data(negative.to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn spi_write_error_surfaces_from_display_img() {
+        let mut di = SPIInterfaceAutoCS::new(FailingSpi, DummyPin);
+        assert!(matches!(
+            di.send_data(&[0]),
+            Err(DisplayError::BusWriteError)
+        ));
+        let mut display = St7789Builder::new().build(di).unwrap();
+        let img = St7789Img::new(240, 240, ColorDepth::Bits16);
+        assert!(matches!(
+            display.display_img(&img),
+            Err(Error::DisplayError)
+        ));
+    }
+
+    #[test]
+    fn nop_sends_nop_command() {
+        let mut display = mock_display();
+        display.nop().unwrap();
+        display.nop_delay(2).unwrap();
+        assert_eq!(display.di.ops(), vec![MockOp::Command(0x00); 3]);
+    }
+
+    #[test]
+    fn progress_callback_called_per_chunk() {
+        let mut display = mock_display();
+        let img = St7789Img::new(240, 240, ColorDepth::Bits16);
+        let calls = std::cell::RefCell::new(Vec::new());
+        display
+            .display_img_with_progress(&img, |sent, total| calls.borrow_mut().push((sent, total)))
+            .unwrap();
+        let calls = calls.into_inner();
+        let total = 240 * 240 * 2;
+        // 115200 bytes: 28 full chunks and one of 512 bytes
+        assert_eq!(calls.len(), 29);
+        assert_eq!(calls[0], (CHUNK_SIZE, total));
+        assert_eq!(calls.last(), Some(&(total, total)));
+    }
+
+    #[test]
+    fn alpha_composited_over_background() {
+        let mut img = St7789Img::new(2, 1, ColorDepth::Bits18);
+        let image = RgbaImage::from_fn(2, 1, |x, _| match x {
+            0 => Rgba([255, 0, 0, 0]),       // fully transparent
+            _ => Rgba([255, 255, 255, 128]), // about 50%
+        });
+        img.set_image_with_alpha_bg(&image, Rgba([0, 0, 255, 255]));
+        // RGB666 keeps the upper 6 bits of each channel
+        assert_eq!(img.get_rgb(0, 0), (0, 0, 252));
+        assert_eq!(img.get_rgb(1, 0), (128, 128, 252));
+    }
+
+    #[test]
+    fn rgb565_round_trip_within_quantization() {
+        let image = RgbaImage::from_fn(256, 3, |x, y| {
+            let v = x as u8;
+            Rgba([v, v.wrapping_mul(3).wrapping_add(y as u8), !v, 255])
+        });
+        let mut img = St7789Img::new(256, 3, ColorDepth::Bits16);
+        img.set_image_depth(&image, ColorDepth::Bits16);
+        let decoded = img.to_rgba_image();
+        for (p, q) in image.pixels().zip(decoded.pixels()) {
+            // 5 bits for red and blue, 6 bits for green: low bits are dropped
+            for (c, max_err) in [(0, 7), (1, 3), (2, 7)] {
+                assert!(q[c] <= p[c] && p[c] - q[c] <= max_err, "{p:?} -> {q:?}");
+            }
+            assert_eq!(q[3], 255);
+        }
+    }
+
+    #[test]
+    fn compare_and_update_skips_unchanged_content() {
+        let mut img = St7789Img::new(4, 4, ColorDepth::Bits16);
+        let current = RgbaImage::from_pixel(4, 4, Rgba([255, 255, 255, 255]));
+        let calls = std::cell::Cell::new(0);
+        let update = |_: &mut RgbaImage| calls.set(calls.get() + 1);
+        assert!(img.compare_and_update(&current, update));
+        assert!(!img.compare_and_update(&current, update));
+        assert_eq!(calls.get(), 1);
+        // Any other write to the buffer forces the next update
+        img.set_pixel(0, 0, 0);
+        assert!(img.compare_and_update(&current, update));
+        assert_eq!(img.get_pixel(0, 0), 0xffff);
+    }
+
+    #[test]
+    fn verify_init_sequence_reports_mismatches() {
+        // MockInterface reads as zero, like a display without MISO.
+        // MADCTL of Rot0 is zero, so only COLMOD differs.
+        let mut display = mock_display();
+        let mismatches = display.verify_init_sequence().unwrap();
+        assert_eq!(
+            mismatches,
+            [(ST7789_RDDCOLMOD, 0, ColorDepth::Bits16.colmod())]
+        );
+        assert_eq!(
+            display.di.ops(),
+            [
+                MockOp::Command(ST7789_RDDCOLMOD),
+                MockOp::Command(ST7789_RDDMADCTL),
+            ]
+        );
+    }
+
+    #[test]
+    fn partial_mode_sends_area_then_on() {
+        let mut display = mock_display();
+        display.enable_partial_mode(10, 119).unwrap();
+        display.disable_partial_mode().unwrap();
+        assert_eq!(
+            display.di.ops(),
+            [
+                MockOp::Command(ST7789_PTLAR),
+                MockOp::Data(vec![0, 10, 0, 119]),
+                MockOp::Command(ST7789_PTLON),
+                MockOp::Command(ST7789_NORON),
+            ]
+        );
+        assert!(display.enable_partial_mode(20, 10).is_err());
+    }
+
+    #[test]
+    fn flush_dirty_error_reaches_callback() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+        use std::sync::Arc;
+
+        let di = SPIInterfaceAutoCS::new(FailingSpi, DummyPin);
+        let mut display = St7789Builder::new().build(di).unwrap();
+        let errors = Arc::new(AtomicU32::new(0));
+        let count = Arc::clone(&errors);
+        display.set_on_display_error(Some(Box::new(move |_: &Error| {
+            count.fetch_add(1, Ordering::Relaxed);
+        })));
+        let mut img = St7789Img::new(240, 240, ColorDepth::Bits16);
+        img.set_pixel(0, 0, 0xffff);
+        assert!(display.flush_dirty(&mut img).is_err());
+        assert_eq!(errors.load(Ordering::Relaxed), 1);
+        assert!(img.dirty_regions().next().is_some());
+    }
 }
@@ -1,32 +1,53 @@
 //! Volumio TFT st7789 viewer
 
-use st7789volumio::control::SPIInterfaceAutoCS;
-use st7789volumio::{St7789, St7789Img, ROTATION};
+mod icons;
 
-use chrono::Local;
+use st7789volumio::control::{
+    DataCommand, MockInterface, RppalSPIInterfaceAutoCS, SpiMode, WriteOnlyDataCommand,
+};
+use st7789volumio::{
+    grey_image_in_place, register_for_voltage, AddressingMode, ColorDepth, Gamma, St7789,
+    St7789Builder, St7789Img, DEF_VDVS, DEF_VRHS, GAMMA_PRESET_1, GAMMA_PRESET_2, GAMMA_PRESET_3,
+    GAMMA_PRESET_4, ROTATION, VDVS_TABLE, VRHS_TABLE,
+};
+
+use chrono::{Local, Timelike};
 use image::imageops;
 use image::imageops::FilterType;
 use image::{GenericImageView, Rgba, RgbaImage};
-use imageproc::drawing::{draw_filled_rect_mut, draw_hollow_rect_mut, draw_text_mut};
+use imageproc::drawing::{
+    draw_filled_circle_mut, draw_filled_rect_mut, draw_hollow_rect_mut, draw_line_segment_mut,
+    draw_text_mut,
+};
+use imageproc::pixelops::weighted_sum;
 use imageproc::rect::Rect;
 use libc::{c_int, c_void, exit};
-use rppal::spi;
+use qrcode::{EcLevel, QrCode};
 use rppal::{
     gpio::Gpio,
     spi::{Bus, SlaveSelect, Spi},
 };
-use rusttype::{point, Font, Scale};
-use serde::Deserialize;
+use rusttype::{point, Font, GlyphId, PositionedGlyph, Scale};
+use serde::{Deserialize, Serialize};
 use serde_aux::prelude::*;
 use serde_with::*;
 use spectrum_analyzer::scaling::divide_by_N;
 use spectrum_analyzer::windows::hann_window;
-use spectrum_analyzer::{samples_fft_to_spectrum, FrequencyLimit};
+use spectrum_analyzer::{samples_fft_to_spectrum, Frequency, FrequencyLimit, FrequencyValue};
 use std::{
+    cmp,
+    collections::{HashMap, VecDeque},
     env,
     ffi::CString,
     fs,
+    io::{BufRead, BufReader, Read, Write},
+    mem,
+    net::{TcpListener, UdpSocket},
+    path::{Path, PathBuf},
     str::FromStr,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    sync::{Arc, Mutex},
     thread,
     time::{Duration, Instant},
 };
@@ -37,13 +58,32 @@ use std::{
 
 const INFO_FONT: &str = "/home/volumio/.local/share/fonts/TakaoPGothic.ttf";
 const NUM_FONT: &str = "/home/volumio/.local/share/fonts/led_digital_7.ttf";
+const FALLBACK_FONT: &str = "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
+const FONT_DIRS: [&str; 3] = [
+    "/usr/share/fonts",
+    "/home/volumio/.local/share/fonts",
+    "/usr/local/share/fonts",
+];
+
+const VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "-", env!("GIT_HASH"));
+const BUILD_DATE: &str = env!("BUILD_DATE");
+const SPLASH_MSEC: u64 = 2000;
 
 const INFO_INTERVAL_SEC: u64 = 2;
+// for adaptive polling
+const INFO_PLAY_INTERVAL_SEC: u64 = 5;
+const INFO_IDLE_INTERVAL_SEC: u64 = 10;
+const INFO_RETRY_INTERVAL_SEC: u64 = 30;
+// by API error class
+const API_REFUSED_RETRY_SEC: u64 = 1; // Volumio is still booting
+const API_TIMEOUT_RETRY_SEC: u64 = 10;
 const DISP_INTERVAL_MSEC: u64 = 20;
-const CLOCK_INTERVAL_MSEC: u64 = 1000;
+const CLOCK_INTERVAL_MSEC: u64 = 200; // redrawn only when the second changes
+const STOP_INTERVAL_MSEC: u64 = 1000;
 
 const DISP_WIDTH: u32 = 240;
 const DISP_HEIGHT: u32 = 240;
+const DEF_ROTATION: ROTATION = ROTATION::Rot180;
 
 const DISP_AREA_WIDTH: u32 = 232;
 const DISP_AREA_HEIGHT: u32 = 232;
@@ -51,21 +91,50 @@ const DISP_AREA_HEIGHT: u32 = 232;
 const DISP_AREA_MARGIN_X: i32 = 4;
 const DISP_AREA_MARGIN_Y: i32 = 4;
 
+// Border around DISP_AREA (in the margin) on pause/play
+const TRANSITION_FRAMES: u8 = 10;
+const TRANSITION_STROKE: u32 = 4;
+const ZOOM_FRAMES: u8 = 20;
+const BRIGHTNESS_STEP: i16 = 16;
+
 const THUMB_WIDTH: u32 = 120;
 const THUMB_HEIGHT: u32 = 120;
 const THUMB_X: i32 = 4;
 const THUMB_Y: i32 = 116;
+const THUMB_BLUR_SIGMA: f32 = 3.0;
+
+// Dominant colors of album art (no room below the thumbnail, so inside it)
+const PALETTE_K: usize = 4;
+const PALETTE_SIZE: u32 = 10;
+const PALETTE_MARGIN: u32 = 2;
+const PALETTE_X: i32 = 4; // relative to thumbnail
+const PALETTE_Y: i32 = (THUMB_HEIGHT - PALETTE_SIZE - 4) as i32;
+const PALETTE_ITERATIONS: usize = 10;
+const THEME_MIN_CONTRAST: f32 = 96.0; // in luma (0-255)
 
 const SEEK_WIDTH: u32 = 232;
 const SEEK_HEIGHT: u32 = 8;
+const SEEK_MIN_HEIGHT: u32 = 2;
+const SEEK_MAX_HEIGHT: u32 = 20;
 const SEEK_X: i32 = 4;
 const SEEK_Y: i32 = 42;
+const SEEK_GAP: i32 = 2; // between progress text and seek bar
+
+const PROGRESS_WIDTH: u32 = SEEK_WIDTH;
+const PROGRESS_HEIGHT: u32 = 16;
+const PROGRESS_X: i32 = SEEK_X;
 
 const CPU_THM_WIDTH: u32 = 106;
 const CPU_THM_HEIGHT: u32 = 22;
 const CPU_THM_X: i32 = 134;
 const CPU_THM_Y: i32 = 192;
 const CPU_THM_FILE: &str = "/sys/class/thermal/thermal_zone0/temp";
+// Temperature history graph (one reading per second) left of the current value
+const CPU_SPARK_WIDTH: u32 = 40;
+const CPU_SPARK_HEIGHT: u32 = 14;
+const CPU_SPARK_Y: i32 = CPU_THM_Y + (CPU_THM_HEIGHT - CPU_SPARK_HEIGHT) as i32 / 2;
+const CPU_HISTORY_LEN: usize = CPU_SPARK_WIDTH as usize;
+const CPU_LABEL_X: i32 = CPU_THM_X + CPU_SPARK_WIDTH as i32 + 4;
 
 const AUDIO_WIDTH: u32 = 106;
 const AUDIO_HEIGHT: u32 = 26;
@@ -87,13 +156,57 @@ const ARTIST_INFO_HEIGHT: u32 = 26;
 const ARTIST_INFO_X: i32 = DISP_AREA_MARGIN_X;
 const ARTIST_INFO_Y: i32 = 84;
 
+const QUEUE_INFO_WIDTH: u32 = 30;
+const QUEUE_INFO_HEIGHT: u32 = 16;
+const QUEUE_INFO_X: i32 = ALBUM_INFO_X + ALBUM_INFO_WIDTH as i32 - QUEUE_INFO_WIDTH as i32;
+const QUEUE_INFO_Y: i32 = ALBUM_INFO_Y;
+const QUEUE_INTERVAL_SEC: u64 = 30;
+
 const DATE_INFO_X: i32 = 20;
 const DATE_INFO_Y: i32 = 20;
 const TIME_INFO_X: i32 = 40;
 const TIME_INFO_Y: i32 = 80;
+const SESSION_INFO_X: i32 = 20;
+const SESSION_INFO_Y: i32 = 140;
+// QR code of Web UI URL at right of the time.
+// "http://" and IPv4 address fit in version 2 (25 modules): 58px with quiet zone
+const QR_MODULE_SIZE: u32 = 2;
+const QR_QUIET_ZONE: u32 = 2; // modules
+const QR_X: i32 = 166;
+const QR_Y: i32 = 96;
+
+const NET_ICON_WIDTH: u32 = 8;
+const NET_ICON_HEIGHT: u32 = 5;
+const NET_ICON_X: i32 = 228;
+const NET_ICON_Y: i32 = 4;
+const NET_ICON_STALE_SEC: u64 = 10;
+// Line from, to in pixels relative to the net icon
+type Segment = ((f32, f32), (f32, f32));
+
+const FREEZE_ICON_WIDTH: u32 = 4;
+const FREEZE_ICON_HEIGHT: u32 = PROGRESS_HEIGHT;
+const FREEZE_ICON_X: i32 = PROGRESS_X + PROGRESS_WIDTH as i32 - 8;
+const FREEZE_SEC: u64 = 5;
+
+// Lyrics replace album and artist info
+const LYRICS_X: i32 = ALBUM_INFO_X;
+const LYRICS_Y: i32 = ALBUM_INFO_Y;
+const LYRICS_WIDTH: u32 = DISP_AREA_WIDTH;
+const LYRICS_HEIGHT: u32 = ALBUM_INFO_HEIGHT + ARTIST_INFO_HEIGHT;
+const LYRICS_START_SEC: u32 = 20;
+const LYRICS_LINE_SEC: u32 = 4;
+
+const CLOCK_PARTIAL_START: u16 = 10;
+const CLOCK_PARTIAL_END: u16 = 170;
+
+const STOP_CLOCK_X: i32 = 170;
+const STOP_CLOCK_Y: i32 = 210;
 
 const MDP_BASE_URL: &str = "http://127.0.0.1:3000";
 const GET_STATE_API: &str = "/api/v1/getstate";
+const COMMANDS_API: &str = "/api/v1/commands/?cmd=";
+const LYRICS_API: &str = "/api/v1/lyrics";
+const QUEUE_API: &str = "/api/v1/getQueue";
 
 const DEF_SPI_BUS: u8 = 0;
 const DEF_CS_PIN: u8 = 0;
@@ -101,10 +214,23 @@ const DEF_GPIO_DC_PIN: u8 = 25;
 const DEF_GPIO_RST_PIN: u8 = 27;
 const DEF_GPIO_BLK_PIN: u8 = 24;
 
+// for --auto-detect
+const DT_ROOT: &str = "/proc/device-tree";
+const DT_MAX_DEPTH: u32 = 4;
+const BOOT_CONFIG_FILES: [&str; 3] = [
+    "/boot/userconfig.txt",
+    "/boot/config.txt",
+    "/boot/firmware/config.txt",
+];
+
 const SPI_MAXSPEED_HZ: u32 = 48_000_000;
+const DEF_STARTUP_RETRIES: u32 = 3;
+const DEF_API_TIMEOUT_SEC: u64 = 3;
 
 const MPD_FIFO_FILE: &str = "/tmp/snapfifo";
-const FQ: u32 = 44100;
+const SNAPSHOT_FILE: &str = "/var/cache/st7789volumio/state.json";
+const DEF_SP_SAMPLE_RATE: u32 = 44100;
+const DEF_SP_HP_ALPHA: f32 = 0.97;
 const DATA_BIT_LEN: usize = 16;
 const FQ_MAX: f64 = 20000.0f64;
 const FQ_MIN: f64 = 50.0f64;
@@ -116,12 +242,45 @@ const SP_Y: i32 = 116;
 const SP_WIDTH: u32 = 108;
 const SP_HEIGHT: u32 = 48;
 
-const SP_BAR_WIDTH: i32 = 4;
+const SNR_X: i32 = 134;
+const SNR_Y: i32 = 168;
+const SNR_WIDTH: u32 = 106;
+const SNR_HEIGHT: u32 = 20;
+const SNR_UPDATE_DB: f64 = 3.0;
+const SILENCE_LEVEL: f64 = 0.000_01; // -100dB
+const NUM_NOISE_BARS: usize = 3;
+
 const SP_BAR_MARGIN: i32 = 1;
 const NUM_BARS: usize = 16;
+const DEF_SP_DUMP_FRAMES: u32 = 1000;
+const MIN_NUM_BARS: usize = 4;
+const MAX_NUM_BARS: usize = SP_WIDTH as usize / 2;
+// --mode spectrum-full
+const FULL_SP_BARS: usize = 40;
+const FULL_SP_THUMB_SIZE: u32 = 40;
+// Grid lines of spectrum
+const SP_GRID_FQ: [f64; 3] = [100.0, 1000.0, 10000.0];
+const SP_GRID_DB: [u32; 3] = [20, 40, 60]; // below the top
+const SP_DYNAMIC_RANGE_DB: u32 = 90;
 
-const SIGNAL16_BUFFLEN: usize = FQ as usize;
+// 1/3-octave bands (ISO 266)
+const OCT_CENTER_FQ: [f64; 31] = [
+    20.0, 25.0, 31.5, 40.0, 50.0, 63.0, 80.0, 100.0, 125.0, 160.0, 200.0, 250.0, 315.0, 400.0,
+    500.0, 630.0, 800.0, 1000.0, 1250.0, 1600.0, 2000.0, 2500.0, 3150.0, 4000.0, 5000.0, 6300.0,
+    8000.0, 10000.0, 12500.0, 16000.0, 20000.0,
+];
+// 31 x (3px + 1px margin) = 124px exceeds SP_WIDTH (108px), so 2px: 31 x 3px = 93px
+const OCT_BAR_WIDTH: i32 = 2;
+const OCT_BAR_MARGIN: i32 = 1;
+
+const SIGNAL16_BUFF_SEC: f32 = 1.0; // Buffer duration
 const DEF_VZ_OFFSET: u32 = 500; // Default 500msec
+const AUDIO_TIMEOUT_MSEC: u32 = 500; // Flatten spectrum after no data
+
+const UDP_BUFF_LEN: usize = 512;
+const SCREENSHOT_TIMEOUT_SEC: u64 = 3;
+const MJPEG_FRAME_MSEC: u128 = 100; // 10 fps
+const MJPEG_QUALITY: u8 = 80;
 
 ///
 /// Globals
@@ -130,17 +289,381 @@ const DEF_VZ_OFFSET: u32 = 500; // Default 500msec
 static COLOR_BLACK: Rgba<u8> = Rgba::<u8>([0u8, 0u8, 0u8, 255u8]);
 static COLOR_WHITE: Rgba<u8> = Rgba::<u8>([255u8, 255u8, 255u8, 255u8]);
 static COLOR_GREY: Rgba<u8> = Rgba::<u8>([120u8, 120u8, 120u8, 255u8]);
+static COLOR_PLACEHOLDER_DARK: Rgba<u8> = Rgba::<u8>([40u8, 40u8, 40u8, 255u8]);
 static COLOR_LIGHTBLUE: Rgba<u8> = Rgba::<u8>([176u8, 224u8, 255u8, 255u8]);
+static COLOR_GREEN: Rgba<u8> = Rgba::<u8>([0u8, 200u8, 0u8, 255u8]);
+static COLOR_ORANGE: Rgba<u8> = Rgba::<u8>([255u8, 140u8, 0u8, 255u8]);
+static COLOR_RED: Rgba<u8> = Rgba::<u8>([255u8, 0u8, 0u8, 255u8]);
+static COLOR_TRANSPARENT: Rgba<u8> = Rgba::<u8>([0u8, 0u8, 0u8, 0u8]);
 
 static COLOR_SP_BAR: Rgba<u8> = Rgba::<u8>([0u8, 255u8, 120u8, 255u8]);
+static COLOR_SP_PEAK: Rgba<u8> = Rgba::<u8>([255u8, 60u8, 0u8, 255u8]);
+static COLOR_SP_GRID: Rgba<u8> = Rgba::<u8>([30u8, 30u8, 30u8, 255u8]);
 
 ///
 /// Data-type definitions.
 ///
 
+/// Command-line configuration
+#[derive(Debug)]
+pub struct Config {
+    pub spi: u8,
+    pub cs: u8,
+    pub dc: u8,
+    pub rst: u8,
+    pub blk: u8,
+    pub vz: u8,
+    pub vz_offset: u32,
+    pub udp_port: Option<u16>,
+    pub health_port: Option<u16>,
+    pub simulate_port: Option<u16>,
+    pub api_timeout_secs: u64,
+    pub reset_session_on_stop: bool,
+    pub auto_recover_freeze: bool,
+    pub show_lyrics: bool,
+    pub adaptive_polling: bool,
+    pub sleep_after_min: u32,
+    pub enable_transitions: bool,
+    pub zoom_album_art: bool,
+    pub horizontal_flip: bool,
+    pub vertical_flip: bool,
+    pub stdin_control: bool,
+    pub adaptive_theme: bool,
+    pub show_qr_code: bool,
+    pub seek_bar_height: u32,
+    pub seek_bar_corner_radius: u32,
+    pub info_fonts: Vec<String>,
+    pub num_fonts: Vec<String>,
+    pub fallback_fonts: Vec<String>,
+    pub hint_text: bool,
+    pub sp_sample_rate: u32,
+    pub sp_mode: SpMode,
+    pub spectrum_style: SpectrumStyle,
+    pub spectrum_grid: bool,
+    pub display_mode: DisplayMode,
+    pub sp_hp_alpha: f32,
+    pub sp_bars: usize,
+    pub sp_dump_csv: Option<String>,
+    pub sp_dump_frames: u32,
+    pub x_offset: u16,
+    pub y_offset: u16,
+    pub lcm_ctrl: Option<u8>,
+    pub gctrl: Option<u8>,
+    pub vrhs: u8,
+    pub vdvs: u8,
+    pub gamma_preset: Option<Gamma>,
+    pub gamma_positive: Option<[u8; 14]>,
+    pub gamma_negative: Option<[u8; 14]>,
+    pub startup_delay_ms: u64,
+    pub startup_retries: u32,
+    pub spi_mode: SpiMode,
+    pub addr_mode: AddressingMode,
+    pub color_depth: ColorDepth,
+    pub error_mode: ErrorMode,
+    pub genre_rotations: HashMap<String, ROTATION>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            spi: DEF_SPI_BUS,
+            cs: DEF_CS_PIN,
+            dc: DEF_GPIO_DC_PIN,
+            rst: DEF_GPIO_RST_PIN,
+            blk: DEF_GPIO_BLK_PIN,
+            vz: 0, // Default Off
+            vz_offset: DEF_VZ_OFFSET,
+            udp_port: None,
+            health_port: None,
+            simulate_port: None,
+            api_timeout_secs: DEF_API_TIMEOUT_SEC,
+            reset_session_on_stop: false,
+            auto_recover_freeze: false,
+            show_lyrics: false,
+            adaptive_polling: false,
+            sleep_after_min: 0,
+            enable_transitions: true,
+            zoom_album_art: false,
+            horizontal_flip: false,
+            vertical_flip: false,
+            stdin_control: false,
+            adaptive_theme: false,
+            show_qr_code: false,
+            seek_bar_height: SEEK_HEIGHT,
+            seek_bar_corner_radius: 0,
+            info_fonts: vec![INFO_FONT.to_string(), FALLBACK_FONT.to_string()],
+            num_fonts: vec![NUM_FONT.to_string(), FALLBACK_FONT.to_string()],
+            fallback_fonts: vec![FALLBACK_FONT.to_string()],
+            hint_text: false,
+            sp_sample_rate: DEF_SP_SAMPLE_RATE,
+            sp_mode: SpMode::Log,
+            spectrum_style: SpectrumStyle::BottomUp,
+            spectrum_grid: false,
+            display_mode: DisplayMode::Auto,
+            sp_hp_alpha: DEF_SP_HP_ALPHA,
+            sp_bars: NUM_BARS,
+            sp_dump_csv: None,
+            sp_dump_frames: DEF_SP_DUMP_FRAMES,
+            x_offset: 0,
+            y_offset: 0,
+            lcm_ctrl: None,
+            gctrl: None,
+            vrhs: DEF_VRHS,
+            vdvs: DEF_VDVS,
+            gamma_preset: None,
+            gamma_positive: None,
+            gamma_negative: None,
+            startup_delay_ms: 0,
+            startup_retries: DEF_STARTUP_RETRIES,
+            spi_mode: SpiMode::Mode3,
+            addr_mode: AddressingMode::Bit16,
+            color_depth: ColorDepth::Bits16,
+            error_mode: ErrorMode::Ignore,
+            genre_rotations: HashMap::new(),
+        }
+    }
+}
+
+/// Display mode selected by remote command
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DisplayMode {
+    /// Follow Volumio status
+    Auto,
+    Clock,
+    Music,
+    /// Like Auto, but spectrum fills the display while playing
+    SpectrumFull,
+}
+
+impl FromStr for DisplayMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(DisplayMode::Auto),
+            "clock" => Ok(DisplayMode::Clock),
+            "music" => Ok(DisplayMode::Music),
+            "spectrum-full" => Ok(DisplayMode::SpectrumFull),
+            _ => Err(format!("Unknown display mode: {s}")),
+        }
+    }
+}
+
+/// What to draw in the frame
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DrawMode {
+    Clock,
+    Music,
+    Stop,
+    SpectrumFull,
+}
+
+/// Regions of music screen overlaid only when dirty.
+/// Spectrum changes every frame, and seek bar, audio info, CPU temperature
+/// and album art are drawn only when changed by themselves.
+#[derive(Copy, Clone, Debug)]
+pub enum Region {
+    Title,
+    Album,
+    Artist,
+}
+const NUM_REGIONS: usize = 3;
+
+/// Writing direction of text
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextDirection {
+    Ltr,
+    Rtl,
+}
+
+/// Application mode
+///
+/// ```text
+///                 +--------------+
+///                 | Initializing |
+///                 +--------------+
+///                        |
+///                        v
+///   +---------+     +---------+     +---------+
+///   | Stopped |<--->|  Clock  |<--->| Playing |
+///   +---------+     +---------+     +---------+
+///        ^               ^               ^
+///        |               v               |
+///        |          +---------+          |
+///        +--------->| Paused  |<---------+
+///                   +---------+
+///  (Stopped <-> Playing as well)
+/// ```
+///
+/// Modes follow Volumio status, or MODE command from remote.
+/// Screensaver and Menu are reserved and not entered yet.
+#[derive(Debug, Clone)]
+pub enum AppMode {
+    Initializing,
+    Clock,
+    /// Info when entered
+    Playing(Info),
+    /// Info when entered
+    Paused(Info),
+    Stopped,
+    Screensaver,
+    /// Selected item
+    Menu(usize),
+}
+
+impl AppMode {
+    /// Mode for Volumio status.
+    pub fn from_info(info: &Info) -> AppMode {
+        if info.is_playing() {
+            AppMode::Playing(info.clone())
+        } else if info.is_paused() {
+            AppMode::Paused(info.clone())
+        } else if info.is_stopped() {
+            AppMode::Stopped
+        } else {
+            AppMode::Clock
+        }
+    }
+
+    /// Music screen is shown.
+    pub fn is_music(&self) -> bool {
+        matches!(
+            self,
+            AppMode::Playing(_) | AppMode::Paused(_) | AppMode::Menu(_)
+        )
+    }
+
+    /// What to draw in the mode.
+    pub fn draw_mode(&self) -> DrawMode {
+        match self {
+            AppMode::Playing(_) | AppMode::Paused(_) | AppMode::Menu(_) => DrawMode::Music,
+            AppMode::Stopped => DrawMode::Stop,
+            AppMode::Initializing | AppMode::Clock | AppMode::Screensaver => DrawMode::Clock,
+        }
+    }
+}
+
+/// Commands received from remote (UDP) or stdin
+#[derive(Debug)]
+pub enum DisplayCommand {
+    SetBrightness(u8),
+    /// Relative to current brightness
+    AdjustBrightness(i16),
+    SetRotation(ROTATION),
+    /// Rotate 90 degrees clockwise from current rotation
+    Rotate,
+    SetMode(DisplayMode),
+    Clear,
+    /// Number of spectrum bars (log mode)
+    SetNumBars(usize),
+    /// Volumio player command (toggle, next, prev, ...)
+    Player(&'static str),
+    Quit,
+}
+
+/// Restores terminal settings on drop.
+pub struct RawModeGuard {
+    orig: libc::termios,
+}
+
+/// Single key commands from stdin (development without hardware input).
+pub struct StdinController {
+    #[allow(dead_code)]
+    raw_mode_guard: RawModeGuard,
+}
+
+/// Parse rotation in degrees.
+fn parse_rotation(deg: &str) -> Option<ROTATION> {
+    match deg.parse::<u32>().ok()? {
+        0 => Some(ROTATION::Rot0),
+        90 => Some(ROTATION::Rot90),
+        180 => Some(ROTATION::Rot180),
+        270 => Some(ROTATION::Rot270),
+        _ => None,
+    }
+}
+
+impl DisplayCommand {
+    /// Parse a command line like "BRIGHTNESS 128".
+    pub fn parse(line: &str) -> Option<DisplayCommand> {
+        let mut words = line.split_whitespace();
+        let cmd = words.next()?.to_ascii_uppercase();
+        let param = words.next();
+
+        match cmd.as_str() {
+            "BRIGHTNESS" => param?.parse::<u8>().ok().map(DisplayCommand::SetBrightness),
+            "ROTATION" => parse_rotation(param?).map(DisplayCommand::SetRotation),
+            "MODE" => param?.parse().ok().map(DisplayCommand::SetMode),
+            "CLEAR" => Some(DisplayCommand::Clear),
+            "BARS" => param?.parse::<usize>().ok().map(DisplayCommand::SetNumBars),
+            _ => None,
+        }
+    }
+}
+
+impl RawModeGuard {
+    /// Disable line buffering and echo of stdin.
+    /// Signals (Ctrl-C) and output processing are kept unlike cfmakeraw.
+    pub fn new() -> std::io::Result<RawModeGuard> {
+        let mut term = mem::MaybeUninit::<libc::termios>::uninit();
+        unsafe {
+            if libc::tcgetattr(libc::STDIN_FILENO, term.as_mut_ptr()) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let orig = term.assume_init();
+            let mut raw = orig;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(RawModeGuard { orig })
+        }
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.orig);
+        }
+    }
+}
+
+impl StdinController {
+    /// Read keys in background and pass them to main loop as commands.
+    /// p: play/pause, n: next, b: previous, q: quit, +/-: brightness, r: rotate
+    pub fn start(tx: Sender<DisplayCommand>) -> std::io::Result<StdinController> {
+        let raw_mode_guard = RawModeGuard::new()?;
+
+        thread::spawn(move || {
+            let mut buf = [0u8; 1];
+            // Blocking read is fine in this thread
+            while std::io::stdin().read_exact(&mut buf).is_ok() {
+                let cmd = match buf[0] {
+                    b'p' => DisplayCommand::Player("toggle"),
+                    b'n' => DisplayCommand::Player("next"),
+                    b'b' => DisplayCommand::Player("prev"),
+                    b'q' => DisplayCommand::Quit,
+                    b'+' => DisplayCommand::AdjustBrightness(BRIGHTNESS_STEP),
+                    b'-' => DisplayCommand::AdjustBrightness(-BRIGHTNESS_STEP),
+                    b'r' => DisplayCommand::Rotate,
+                    _ => continue,
+                };
+                if tx.send(cmd).is_err() {
+                    // main loop has gone
+                    return;
+                }
+            }
+        });
+        Ok(StdinController { raw_mode_guard })
+    }
+}
+
 /// Volumio info
 #[serde_as]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Info {
     pub status: String,
     #[serde(default)]
@@ -160,12 +683,73 @@ pub struct Info {
     pub bitdepth: String,
     #[serde(default)]
     pub channels: u32,
+    /// Bitrate in kbps. Only given for lossy formats.
+    #[serde(default, deserialize_with = "deserialize_bitrate")]
+    pub bitrate: Option<u32>,
     pub seek: u32,
     #[serde(default)]
     pub duration: u32,
+    #[serde(default, rename = "trackType")]
+    #[serde_as(as = "DefaultOnNull")]
+    pub track_type: String,
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnNull")]
+    pub genre: String,
+    /// Index in queue (0-based). None for web radio.
+    #[serde(default)]
+    pub position: Option<u32>,
 }
 
 impl Info {
+    pub fn is_playing(&self) -> bool {
+        self.status == "play"
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.status == "pause"
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.status == "stop"
+    }
+
+    /// Playing or paused.
+    pub fn is_active(&self) -> bool {
+        self.is_playing() || self.is_paused()
+    }
+
+    /// Elapsed time of the track in sec.
+    pub fn elapsed_secs(&self) -> u32 {
+        self.seek / 1000
+    }
+
+    /// Audio quality for display, e.g. "320k MP3", "96/24 FLAC" or "DSD64".
+    pub fn display_quality_string(&self) -> String {
+        let track_type = self.track_type.to_ascii_uppercase();
+        let sr = self.samplerate.split_whitespace().next().unwrap_or("");
+        match track_type.as_str() {
+            "DSF" | "DFF" | "DSD" => {
+                // DSD64 = 64 * 44.1kHz = 2.8224MHz
+                let mhz = match f64::from_str(sr) {
+                    Ok(v) if self.samplerate.to_ascii_lowercase().contains("khz") => v / 1000.0,
+                    Ok(v) => v,
+                    Err(_) => return "DSD".to_string(),
+                };
+                format!("DSD{:.0}", mhz * 1000.0 / 44.1)
+            }
+            "MP3" | "AAC" | "M4A" | "OGG" | "OPUS" if self.bitrate.is_some() => {
+                format!("{}k {}", self.bitrate.unwrap_or(0), track_type)
+            }
+            _ => {
+                let bd = self.bitdepth.split_whitespace().next().unwrap_or("");
+                if sr.is_empty() || bd.is_empty() {
+                    return track_type;
+                }
+                format!("{}/{} {}", sr, bd, track_type)
+            }
+        }
+    }
+
     pub fn new() -> Info {
         Info {
             status: { String::new() },
@@ -176,37 +760,79 @@ impl Info {
             samplerate: { String::new() },
             bitdepth: { String::new() },
             channels: 0,
+            bitrate: None,
             seek: 0,
             duration: 0,
+            track_type: { String::new() },
+            genre: { String::new() },
+            position: None,
         }
     }
 }
 
+/// Lyrics from Volumio lyrics plugin
+#[derive(Deserialize, Debug)]
+pub struct VolumioLyrics {
+    #[serde(default)]
+    pub lyrics: String,
+}
+
+impl VolumioLyrics {
+    /// Fetch lyrics of the track as lines.
+    pub fn fetch(
+        client: &reqwest::blocking::Client,
+        base_url: &str,
+        artist: &str,
+        title: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let res = client
+            .get(format!("{base_url}{LYRICS_API}"))
+            .query(&[("artist", artist), ("title", title)])
+            .send()?
+            .json::<VolumioLyrics>()?;
+        Ok(res
+            .lyrics
+            .split('\n')
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect())
+    }
+}
+
 impl Default for Info {
     fn default() -> Self {
         Self::new()
     }
 }
 
-/// RingBuffer for Signal (Capacity 1sec)
+/// RingBuffer for Signal (Capacity is power of two)
 #[derive(Debug)]
 pub struct RingSignal16Buffer {
     capacity: i32,
     tail: i32,
     length: i32,
     buffer: Vec<u8>,
+    last_read_at: Option<Instant>,
 }
 
 impl RingSignal16Buffer {
     pub fn new(max_entry: usize) -> RingSignal16Buffer {
+        // for 16bit (2byte), round up to power of two for masking
+        let capacity = (max_entry * 2).next_power_of_two();
         RingSignal16Buffer {
-            capacity: (max_entry * 2) as i32, // for 16bit (2byte)
+            capacity: capacity as i32,
             tail: 0,
             length: 0,
-            buffer: vec![0u8; max_entry * 2],
+            buffer: vec![0u8; capacity],
+            last_read_at: None,
         }
     }
 
+    /// Wrap position into buffer
+    fn wrap(&self, pos: i32) -> i32 {
+        pos & (self.capacity - 1)
+    }
+
     /// Clean up
     pub fn clean(&mut self) {
         self.tail = 0;
@@ -226,17 +852,207 @@ impl RingSignal16Buffer {
                 self.length = self.capacity;
             }
         }
-        self.tail = (self.tail + read_bytes) % self.capacity;
+        self.tail = self.wrap(self.tail + read_bytes);
+        self.last_read_at = Some(Instant::now());
     }
 
     /// Get buffer position before entry_num
     pub fn before_pos(&mut self, entry_num: i32) -> Option<i32> {
         if (entry_num <= self.length) && (entry_num <= self.capacity) {
-            Some((self.capacity + self.tail - entry_num * 2) % self.capacity)
+            Some(self.wrap(self.capacity + self.tail - entry_num * 2))
         } else {
             None
         }
     }
+
+    /// Decode samples of the first channel before offset entries into out.
+    /// false if insufficient data.
+    pub fn read_into_f32_slice(&mut self, offset: i32, out: &mut [f32], channels: usize) -> bool {
+        let head = match self.before_pos(offset * channels as i32) {
+            Some(head) => head as usize,
+            None => return false,
+        };
+        let stride = channels * 2;
+
+        // until the end of buffer, and then from the top
+        let n = cmp::min(out.len(), (self.capacity as usize - head) / stride);
+        let (out_head, out_tail) = out.split_at_mut(n);
+        let top = self.wrap((head + n * stride) as i32) as usize;
+        for (buff, out) in [
+            (&self.buffer[head..], out_head),
+            (&self.buffer[top..], out_tail),
+        ] {
+            for (s, o) in buff.chunks_exact(stride).zip(out.iter_mut()) {
+                // little endian for Intel / Arm
+                *o = i16::from_le_bytes([s[0], s[1]]) as f32 / 32767.0;
+            }
+        }
+        true
+    }
+}
+
+/// Spectrum analysis mode
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SpMode {
+    /// Bars divided in log scale
+    Log,
+    /// 1/3-octave bands
+    Octave,
+}
+
+impl FromStr for SpMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "log" => Ok(SpMode::Log),
+            "octave" => Ok(SpMode::Octave),
+            _ => Err(format!("Unknown spectrum mode: {s}")),
+        }
+    }
+}
+
+/// What to do on display errors
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ErrorMode {
+    /// Log and continue
+    Ignore,
+    /// Reset display up to n times in a row, then shut down
+    Retry(u32),
+    /// Shut down display and exit(1) on the first error
+    Shutdown,
+}
+
+impl FromStr for ErrorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "ignore" => Ok(ErrorMode::Ignore),
+            None if s == "shutdown" => Ok(ErrorMode::Shutdown),
+            Some(("retry", n)) => n
+                .parse()
+                .map(ErrorMode::Retry)
+                .map_err(|_| format!("Invalid retry count: {n}")),
+            _ => Err(format!("Unknown error mode: {s}")),
+        }
+    }
+}
+
+/// Failure of Volumio API call
+#[derive(Clone, Debug, PartialEq)]
+pub enum ApiError {
+    ConnectionRefused,
+    Timeout,
+    HttpError(u16),
+    ParseError(String),
+}
+
+impl ApiError {
+    /// Poll interval while the error continues. None to keep the normal one.
+    pub fn retry_secs(&self) -> Option<u64> {
+        match self {
+            ApiError::ConnectionRefused => Some(API_REFUSED_RETRY_SEC),
+            ApiError::Timeout => Some(API_TIMEOUT_RETRY_SEC),
+            _ => None,
+        }
+    }
+}
+
+/// Rendering style of spectrum bars
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SpectrumStyle {
+    /// Grow from the bottom
+    BottomUp,
+    /// Grow from the top
+    TopDown,
+    /// Grow from the center in both directions
+    CenterOut,
+    /// Grow up from the center with 50% reflection below
+    Mirror,
+    /// Grow from the bottom, turning to COLOR_SP_PEAK with height
+    HeightGradient,
+}
+
+impl FromStr for SpectrumStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bottom-up" => Ok(SpectrumStyle::BottomUp),
+            "top-down" => Ok(SpectrumStyle::TopDown),
+            "center-out" => Ok(SpectrumStyle::CenterOut),
+            "mirror" => Ok(SpectrumStyle::Mirror),
+            "height-gradient" => Ok(SpectrumStyle::HeightGradient),
+            _ => Err(format!("Unknown spectrum style: {s}")),
+        }
+    }
+}
+
+/// 1/3-octave band analyzer
+#[derive(Debug)]
+pub struct OctaveBandAnalyzer {
+    center: Vec<f64>,
+    lower: Vec<f64>,
+    upper: Vec<f64>,
+}
+
+impl OctaveBandAnalyzer {
+    pub fn new(sample_rate: u32) -> OctaveBandAnalyzer {
+        let nyquist = sample_rate as f64 / 2.0;
+        let half_band = 2_f64.powf(1.0 / 6.0);
+        OctaveBandAnalyzer {
+            center: OCT_CENTER_FQ.to_vec(),
+            lower: OCT_CENTER_FQ.iter().map(|fc| fc / half_band).collect(),
+            upper: OCT_CENTER_FQ
+                .iter()
+                .map(|fc| (fc * half_band).min(nyquist))
+                .collect(),
+        }
+    }
+
+    pub fn num_bands(&self) -> usize {
+        self.center.len()
+    }
+
+    /// Lowest frequency to analyze
+    pub fn min_frequency(&self) -> f64 {
+        self.lower[0]
+    }
+
+    /// Average in each band.
+    /// Bands narrower than FFT resolution take the nearest bin.
+    pub fn compute_octave_bands(&self, fft_data: &[(Frequency, FrequencyValue)]) -> Vec<f64> {
+        let mut bands = vec![0.0f64; self.num_bands()];
+        if fft_data.is_empty() {
+            return bands;
+        }
+        for (j, band) in bands.iter_mut().enumerate() {
+            let (sum, n) = fft_data
+                .iter()
+                .filter(|(fr, _)| {
+                    let f = fr.val() as f64;
+                    f >= self.lower[j] && f < self.upper[j]
+                })
+                .fold((0.0f64, 0), |(sum, n), (_, v)| {
+                    (sum + v.val() as f64, n + 1)
+                });
+            *band = if n > 0 {
+                sum / n as f64
+            } else {
+                let nearest = fft_data
+                    .iter()
+                    .min_by(|(a, _), (b, _)| {
+                        let da = (a.val() as f64 - self.center[j]).abs();
+                        let db = (b.val() as f64 - self.center[j]).abs();
+                        da.total_cmp(&db)
+                    })
+                    .unwrap();
+                nearest.1.val() as f64
+            };
+        }
+        bands
+    }
 }
 
 /// SpectrumVisualize info
@@ -245,36 +1061,100 @@ pub struct SpInfo {
     fifo_fd: c_int,
     in_amp_max: f64,
     out_amp_max: f64,
+    num_bars: usize,
     cut_off: Vec<f64>,
     signal: Vec<f32>,
     signal16buff: RingSignal16Buffer,
     offset: u32,
+    sample_rate: u32,
+    snr_db: f64,
+    silent: bool,
+    fq_min: f64,
+    octave: Option<OctaveBandAnalyzer>,
+    high_pass_alpha: f32,
 }
 
 impl SpInfo {
-    pub fn new(fifo_fd: c_int, offset_msec: u32) -> SpInfo {
-        let mut offset: u32 = offset_msec * FQ / 1000;
-        if offset > SIGNAL16_BUFFLEN as u32 {
-            offset = SIGNAL16_BUFFLEN as u32;
+    pub fn new(fifo_fd: c_int, offset_msec: u32, sample_rate: u32, mode: SpMode) -> SpInfo {
+        let buffer_len = (sample_rate as f32 * SIGNAL16_BUFF_SEC) as usize;
+
+        let mut offset: u32 = offset_msec * sample_rate / 1000;
+        if offset > buffer_len as u32 {
+            offset = buffer_len as u32;
         }
 
         let mut sp_info = SpInfo {
             fifo_fd,
             in_amp_max: 0_f64,
             out_amp_max: 0_f64,
-            cut_off: vec![0.0f64; NUM_BARS],
+            num_bars: NUM_BARS,
+            cut_off: Vec::new(),
             signal: vec![0.0f32; NUM_SAMPLES],
-            signal16buff: { RingSignal16Buffer::new(SIGNAL16_BUFFLEN * CHANNELS) },
+            signal16buff: { RingSignal16Buffer::new(buffer_len * CHANNELS) },
             offset,
+            sample_rate,
+            snr_db: 0.0f64,
+            silent: true,
+            fq_min: FQ_MIN,
+            octave: None,
+            high_pass_alpha: 0.0f32,
         };
+        if mode == SpMode::Octave {
+            let octave = OctaveBandAnalyzer::new(sample_rate);
+            sp_info.fq_min = octave.min_frequency();
+            sp_info.octave = Some(octave);
+        }
         sp_info.in_amp_max = 2_f64.powf(DATA_BIT_LEN as f64) / 2.0;
         sp_info.out_amp_max = sp_info.in_amp_max / 2.0 / 2_f64.sqrt();
+        sp_info.build_cut_off();
+        sp_info
+    }
+
+    /// Number of bars in log mode (MIN_NUM_BARS to MAX_NUM_BARS).
+    pub fn with_num_bars(mut self, num_bars: usize) -> SpInfo {
+        self.set_num_bars(num_bars);
+        self
+    }
 
-        let border_unit: f64 = (FQ_MAX.log10() - FQ_MIN.log10()) / (NUM_BARS as f64);
-        for j in 0..NUM_BARS {
-            sp_info.cut_off[j] = 10_f64.powf(FQ_MIN.log10() + border_unit * ((j + 1) as f64));
+    /// Change number of bars in log mode. false if out of range.
+    pub fn set_num_bars(&mut self, num_bars: usize) -> bool {
+        if !(MIN_NUM_BARS..=MAX_NUM_BARS).contains(&num_bars) {
+            return false;
+        }
+        self.num_bars = num_bars;
+        self.build_cut_off();
+        true
+    }
+
+    /// Upper frequency of each bar, log-spaced from FQ_MIN to FQ_MAX.
+    fn build_cut_off(&mut self) {
+        let border_unit: f64 = (FQ_MAX.log10() - FQ_MIN.log10()) / (self.num_bars as f64);
+        self.cut_off = (0..self.num_bars)
+            .map(|j| 10_f64.powf(FQ_MIN.log10() + border_unit * ((j + 1) as f64)))
+            .collect();
+    }
+
+    /// Remove DC offset before FFT (alpha 0 to disable).
+    pub fn with_high_pass_filter(mut self, alpha: f32) -> SpInfo {
+        self.high_pass_alpha = alpha;
+        self
+    }
+
+    /// First-order IIR high-pass filter on the signal.
+    /// Each window is read anew from the ring buffer, so the state starts
+    /// from its first sample (no step from the previous window).
+    fn apply_high_pass(&mut self, alpha: f32) {
+        if alpha <= 0.0 || self.signal.is_empty() {
+            return;
+        }
+        let mut prev_input = self.signal[0];
+        let mut prev_output = 0.0f32;
+        for x in self.signal.iter_mut() {
+            let y = alpha * (prev_output + *x - prev_input);
+            prev_input = *x;
+            prev_output = y;
+            *x = y;
         }
-        sp_info
     }
 
     pub fn fft(&mut self, bar_vals: &mut [f64]) {
@@ -297,34 +1177,27 @@ impl SpInfo {
                 read_len == readable_len as isize
             } {}
         }
-        if let Some(head) = self
+        if !self
             .signal16buff
-            .before_pos((self.offset * CHANNELS as u32) as i32)
+            .read_into_f32_slice(self.offset as i32, &mut self.signal, CHANNELS)
         {
-            for i in 0..NUM_SAMPLES {
-                let j = ((head + (i * CHANNELS * 2) as i32) % self.signal16buff.capacity) as usize;
-                // little endian for Intel / Arm
-                self.signal[i] = ((((self.signal16buff.buffer[j + 1] as i16) << 8) & -256i16)
-                    | (self.signal16buff.buffer[j] & 0x0ff) as i16)
-                    as f32
-                    / 32767.0;
-            }
-        } else {
-            for (_, bar) in bar_vals.iter_mut().enumerate().take(NUM_BARS) {
+            for bar in bar_vals.iter_mut() {
                 *bar = 0.0f64;
             }
+            self.update_snr(bar_vals);
             return;
         }
 
+        self.apply_high_pass(self.high_pass_alpha);
         let hann_window = hann_window(&self.signal[..]);
         // calc spectrum
         let spectrum_hann_window = samples_fft_to_spectrum(
             // (windowed) samples
             &hann_window,
             // sampling rate
-            FQ,
+            self.sample_rate,
             // optional frequency limit: e.g. only interested in frequencies 50 <= f <= 150?
-            FrequencyLimit::Range(FQ_MIN as f32, FQ_MAX as f32),
+            FrequencyLimit::Range(self.fq_min as f32, FQ_MAX as f32),
             //FrequencyLimit::All,
             // optional scale
             Some(&divide_by_N),
@@ -332,10 +1205,18 @@ impl SpInfo {
         .unwrap();
 
         let data = spectrum_hann_window.data();
+        if let Some(octave) = &self.octave {
+            let bands = octave.compute_octave_bands(data);
+            for (bar, band) in bar_vals.iter_mut().zip(bands) {
+                *bar = band;
+            }
+            self.update_snr(bar_vals);
+            return;
+        }
         let f_num = data.len();
 
         let mut i: usize = 0;
-        for (j, bar) in bar_vals.iter_mut().enumerate().take(NUM_BARS) {
+        for (j, bar) in bar_vals.iter_mut().enumerate().take(self.num_bars) {
             let mut flg: bool = true;
             let mut k = 0;
             *bar = 0.0f64;
@@ -359,13 +1240,187 @@ impl SpInfo {
                 *bar /= k as f64;
             }
         }
+        self.update_snr(bar_vals);
+    }
+
+    /// Estimate SNR from the dominant bar and the noise floor (lowest bars).
+    fn update_snr(&mut self, bar_vals: &[f64]) {
+        let mut bars: Vec<f64> = bar_vals.to_vec();
+        bars.sort_by(|a, b| a.total_cmp(b));
+
+        let signal = bars.last().copied().unwrap_or(0.0f64);
+        self.silent = signal < SILENCE_LEVEL;
+        if self.silent {
+            self.snr_db = 0.0f64;
+        } else {
+            let n = cmp::min(NUM_NOISE_BARS, bars.len());
+            let noise = bars[..n].iter().sum::<f64>() / n as f64;
+            self.snr_db = 20.0 * (signal.log10() - noise.max(f64::MIN_POSITIVE).log10());
+        }
+    }
+
+    /// Any bytes have been read from fifo in threshold_ms.
+    pub fn has_recent_audio(&self, threshold_ms: u32) -> bool {
+        self.signal16buff
+            .last_read_at
+            .is_some_and(|t| t.elapsed().as_millis() < threshold_ms as u128)
+    }
+
+    /// Flatten spectrum and drop stale signal when audio has stopped.
+    pub fn reset_on_silence(&mut self, bar_vals: &mut [f64]) {
+        if !self.has_recent_audio(AUDIO_TIMEOUT_MSEC) {
+            self.signal16buff.clean();
+            for bar in bar_vals.iter_mut() {
+                *bar = 0.0f64;
+            }
+        }
+    }
+
+    /// Number of bars to display.
+    pub fn num_bars(&self) -> usize {
+        match &self.octave {
+            Some(octave) => octave.num_bands(),
+            None => self.num_bars,
+        }
+    }
+
+    /// Upper frequency of each bar.
+    pub fn cut_offs(&self) -> &[f64] {
+        match &self.octave {
+            Some(octave) => &octave.upper,
+            None => &self.cut_off,
+        }
+    }
+
+    /// Width and margin of a bar to display.
+    pub fn bar_size(&self) -> (i32, i32) {
+        match &self.octave {
+            Some(_) => (OCT_BAR_WIDTH, OCT_BAR_MARGIN),
+            None => {
+                // Fill SP_WIDTH: margin about half of width, at least 1px
+                let n = self.num_bars as i32;
+                let margin = (SP_WIDTH as i32 / (n * 2 - 1) / 2).max(SP_BAR_MARGIN);
+                ((SP_WIDTH as i32 - margin * (n - 1)) / n, margin)
+            }
+        }
+    }
+
+    /// Capture bars of num_frames frames at display interval and write them as CSV.
+    /// Row: timestamp_ms, bar0, bar1, ...
+    pub fn dump_fft_csv(&mut self, path: &Path, num_frames: u32) -> std::io::Result<()> {
+        let start = Instant::now();
+        let mut bar_vals = vec![0.0f64; self.num_bars()];
+        let mut frames: Vec<Vec<f64>> = Vec::with_capacity(num_frames as usize);
+        for _ in 0..num_frames {
+            thread::sleep(Duration::from_millis(DISP_INTERVAL_MSEC));
+            self.fft(&mut bar_vals);
+            let mut row = vec![start.elapsed().as_millis() as f64];
+            row.extend_from_slice(&bar_vals);
+            frames.push(row);
+        }
+
+        let mut file = std::io::BufWriter::new(fs::File::create(path)?);
+        write!(file, "timestamp_ms")?;
+        for j in 0..bar_vals.len() {
+            write!(file, ",bar{j}")?;
+        }
+        writeln!(file)?;
+        for row in frames {
+            let cols: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+            writeln!(file, "{}", cols.join(","))?;
+        }
+        file.flush()
+    }
+
+    /// Estimated SNR in dB of the last spectrum.
+    pub fn estimated_snr_db(&self) -> f64 {
+        self.snr_db
+    }
+
+    /// All bars of the last spectrum are below silence level.
+    pub fn is_silent(&self) -> bool {
+        self.silent
     }
 }
 
+/// Colors of music screen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DisplayTheme {
+    pub title_color: Rgba<u8>,
+    pub seek_bar_color: Rgba<u8>,
+    pub background_color: Rgba<u8>,
+}
+
+impl Default for DisplayTheme {
+    fn default() -> Self {
+        DisplayTheme {
+            title_color: COLOR_LIGHTBLUE,
+            seek_bar_color: COLOR_LIGHTBLUE,
+            background_color: COLOR_BLACK,
+        }
+    }
+}
+
+/// Positions of music screen parts depending on Config
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DisplayLayout {
+    pub seek_y: i32,
+    pub seek_height: u32,
+    pub seek_radius: u32,
+    pub progress_y: i32,
+}
+
+impl DisplayLayout {
+    /// Seek bar keeps its bottom edge of the default height, progress text is above it.
+    /// Taller seek bar moves both up.
+    pub fn new(seek_height: u32, seek_radius: u32) -> Self {
+        let seek_y = SEEK_Y + SEEK_HEIGHT as i32 - seek_height as i32;
+        DisplayLayout {
+            seek_y,
+            seek_height,
+            seek_radius: seek_radius.min(seek_height / 2),
+            progress_y: seek_y - SEEK_GAP - PROGRESS_HEIGHT as i32,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        if config.seek_bar_corner_radius > config.seek_bar_height / 2 {
+            eprintln!(
+                "Warning: --seek-radius {} is over half of --seek-height, use {}",
+                config.seek_bar_corner_radius,
+                config.seek_bar_height / 2
+            );
+        }
+        Self::new(config.seek_bar_height, config.seek_bar_corner_radius)
+    }
+}
+
+impl Default for DisplayLayout {
+    fn default() -> Self {
+        Self::new(SEEK_HEIGHT, 0)
+    }
+}
+
+/// Part of State kept over restart
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StateSnapshot {
+    pub pre_info: Info,
+    pub seek_pos: u32,
+    pub title_x: u32,
+    pub album_x: u32,
+    pub artist_x: u32,
+}
+
 /// Global status
 #[derive(Debug)]
 pub struct State<'a> {
+    mode: AppMode,
+    /// Shared for Volumio API calls (with timeout, connection pooled)
+    http_client: reqwest::blocking::Client,
     pre_info: Info,
+    /// Status changed or screen cleared: redraw album art, seek bar and
+    /// progress on next update_state. Album art is fetched only when its URL
+    /// changes or it is missing, not by this flag.
     mpd_status_change: bool,
 
     baseimg: RgbaImage,
@@ -377,6 +1432,8 @@ pub struct State<'a> {
     title_x: u32,
     album_x: u32,
     artist_x: u32,
+    title_dir: TextDirection,
+    region_dirty: [bool; NUM_REGIONS],
 
     seek_pos: u32,
 
@@ -387,20 +1444,117 @@ pub struct State<'a> {
 
     font_i: Font<'a>,
     font_n: Font<'a>,
+    /// Used for glyphs missing in font_i
+    fallback_fonts: Vec<Font<'a>>,
 
     bar_vals: Vec<f64>,
-}
+    spectrum_style: SpectrumStyle,
+    spectrum_grid: bool,
 
-impl State<'_> {
-    pub fn new() -> State<'static> {
-        State {
-            pre_info: Info::default(),
-            mpd_status_change: true,
-            baseimg: {
-                let mut baseimg = RgbaImage::new(DISP_WIDTH, DISP_HEIGHT);
-                draw_filled_rect_mut(
-                    &mut baseimg,
-                    Rect::at(0, 0).of_size(DISP_WIDTH, DISP_HEIGHT),
+    show_qr_code: bool,
+    /// "http://<IP address>" shown as QR code in clock mode
+    qr_url: Option<String>,
+
+    session_elapsed_secs: u64,
+    /// Second when clock was drawn (u32::MAX to force redraw)
+    last_clock_second: u32,
+    session_elapsed_msec: u64, // less than 1sec
+    session_title_lost: bool,
+    reset_session_on_stop: bool,
+
+    freeze_detected: bool,
+    freeze_seek: u32,
+    freeze_seek_t: Instant,
+    auto_recover_freeze: bool,
+
+    adaptive_polling: bool,
+    info_interval_sec: u64,
+
+    enable_transitions: bool,
+    transition_frames: u8,
+    zoom_album_art: bool,
+    /// Remaining frames of album art zoom-out
+    zoom_frames: u8,
+    /// Zoom-out progress (0.0: album art fills screen, 1.0: normal)
+    zoom: f32,
+
+    show_lyrics: bool,
+    lyrics_cache: HashMap<String, Vec<String>>, // key: artist and title
+    lyrics_rx: Option<Receiver<(String, Vec<String>)>>,
+    lyrics_shown: Option<usize>,
+    lyrics_txt_img: Option<RgbaImage>,
+
+    queue_length: u32,
+    queue_fetched: Option<Instant>,
+    queue_drawn: Option<(u32, u32)>, // (position, length)
+
+    hint_text: bool,
+
+    api_connected: bool,
+    api_error: Option<ApiError>,
+    api_last_success: Instant,
+    cpu_temp_history: VecDeque<f32>,
+    cpu_temp_pushed: Instant,
+    api_icon_drawn: Option<(Option<ApiError>, bool)>, // (error, fresh)
+
+    stop_overlay: RgbaImage,
+
+    thumb_img: Option<RgbaImage>,
+    thumb_blur_img: Option<RgbaImage>, // for pause
+    thumb_dirty: bool,
+    albumart_rx: Option<Receiver<Option<RgbaImage>>>,
+    album_art_loading: bool,
+    /// Album art of the current URL failed to load. Not retried until the URL changes.
+    last_albumart_error: bool,
+    frame_count: u64,
+    palette: Vec<Rgba<u8>>,
+    palette_url: String,
+    title_bg: Rgba<u8>,
+    adaptive_theme: bool,
+    current_theme: DisplayTheme,
+    layout: DisplayLayout,
+
+    snr_shown: Option<f64>, // None: silence
+    snr_drawn: bool,
+
+    genre_rotations: HashMap<String, ROTATION>, // lowercase genre keyword
+    rotation_tx: Option<Sender<DisplayCommand>>,
+}
+
+impl State<'_> {
+    pub fn try_new(
+        info_fonts: &[String],
+        num_fonts: &[String],
+        fallback_fonts: &[String],
+        api_timeout_secs: u64,
+    ) -> Result<State<'static>, Box<dyn std::error::Error>> {
+        let (font_i, font_n) = match (Self::load_font(info_fonts), Self::load_font(num_fonts)) {
+            (Some(font_i), Some(font_n)) => (font_i, font_n),
+            (Some(font_i), None) => {
+                eprintln!("Warning: no number font available, use info font instead");
+                (font_i.clone(), font_i)
+            }
+            (None, Some(font_n)) => {
+                eprintln!("Warning: no info font available, use number font instead");
+                (font_n.clone(), font_n)
+            }
+            (None, None) => return Err("No font available".into()),
+        };
+
+        let http_client = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(api_timeout_secs))
+            .build()?;
+
+        Ok(State {
+            mode: AppMode::Initializing,
+            http_client,
+            pre_info: Info::default(),
+            mpd_status_change: true,
+            baseimg: {
+                let mut baseimg = RgbaImage::new(DISP_WIDTH, DISP_HEIGHT);
+                draw_filled_rect_mut(
+                    &mut baseimg,
+                    Rect::at(0, 0).of_size(DISP_WIDTH, DISP_HEIGHT),
                     COLOR_BLACK,
                 );
                 baseimg
@@ -411,6 +1565,8 @@ impl State<'_> {
             title_x: 0,
             album_x: 0,
             artist_x: 0,
+            title_dir: TextDirection::Ltr,
+            region_dirty: [true; NUM_REGIONS],
             seek_pos: 0,
 
             scale_xl: Scale { x: 48.0, y: 48.0 },
@@ -418,39 +1574,206 @@ impl State<'_> {
             scale_m: Scale { x: 22.0, y: 22.0 },
             scale_s: Scale { x: 14.0, y: 14.0 },
 
-            font_i: Font::try_from_vec(fs::read(INFO_FONT).unwrap()).unwrap(),
-            font_n: Font::try_from_vec(fs::read(NUM_FONT).unwrap()).unwrap(),
+            font_i,
+            fallback_fonts: fallback_fonts
+                .iter()
+                .filter_map(|path| Self::load_font(std::slice::from_ref(path)))
+                .collect(),
+            font_n,
 
             bar_vals: vec![0.0f64; NUM_BARS],
-        }
+            spectrum_style: SpectrumStyle::BottomUp,
+            spectrum_grid: false,
+
+            qr_url: None,
+
+            session_elapsed_secs: 0,
+            last_clock_second: u32::MAX,
+            session_elapsed_msec: 0,
+            session_title_lost: false,
+            reset_session_on_stop: false,
+
+            freeze_detected: false,
+            freeze_seek: 0,
+            freeze_seek_t: Instant::now(),
+            auto_recover_freeze: false,
+
+            adaptive_polling: false,
+            info_interval_sec: INFO_INTERVAL_SEC,
+
+            enable_transitions: true,
+            transition_frames: 0,
+            zoom_album_art: false,
+            zoom_frames: 0,
+            zoom: 1.0,
+
+            show_lyrics: false,
+            lyrics_cache: HashMap::new(),
+            lyrics_rx: None,
+            lyrics_shown: None,
+            lyrics_txt_img: None,
+
+            queue_length: 0,
+            queue_fetched: None,
+            queue_drawn: None,
+
+            hint_text: false,
+
+            api_connected: false,
+            api_error: None,
+            api_last_success: Instant::now(),
+            cpu_temp_history: VecDeque::with_capacity(CPU_HISTORY_LEN),
+            cpu_temp_pushed: Instant::now(),
+            api_icon_drawn: None,
+
+            stop_overlay: RgbaImage::new(DISP_WIDTH, DISP_HEIGHT),
+
+            thumb_img: None,
+            thumb_blur_img: None,
+            thumb_dirty: false,
+            palette: Vec::new(),
+            palette_url: String::new(),
+            title_bg: COLOR_BLACK,
+            adaptive_theme: false,
+            show_qr_code: false,
+            current_theme: DisplayTheme::default(),
+            layout: DisplayLayout::default(),
+            albumart_rx: None,
+            album_art_loading: false,
+            last_albumart_error: false,
+            frame_count: 0,
+
+            snr_shown: None,
+            snr_drawn: false,
+
+            genre_rotations: HashMap::new(),
+            rotation_tx: None,
+        })
+    }
+
+    /// Load the first available font in paths.
+    fn load_font(paths: &[String]) -> Option<Font<'static>> {
+        paths.iter().find_map(|path| match fs::read(path) {
+            Ok(data) => {
+                let font = Font::try_from_vec(data);
+                if font.is_none() {
+                    eprintln!("Warning: invalid font {path}");
+                }
+                font
+            }
+            Err(e) => {
+                eprintln!("Warning: failed to read font {path}: {e}");
+                None
+            }
+        })
     }
 
     /// Calc horizontal and vertical size for text to be draw.
     /// only for single line text.
-    fn calc_text_size(font: &Font, text: &str, scale: Scale) -> (u32, u32) {
+    fn calc_text_size<'f>(
+        font: &Font<'f>,
+        fallbacks: &[Font<'f>],
+        text: &str,
+        scale: Scale,
+        hint: bool,
+    ) -> (u32, u32) {
         if text.is_empty() {
             (0u32, 0u32)
         } else {
             let v_metrics = font.v_metrics(scale);
-            let glyphs: Vec<_> = font.layout(text, scale, point(0.0, 0.0)).collect();
+            let glyphs = Self::layout_glyphs(font, fallbacks, text, scale, hint);
             let glyphs_height = (v_metrics.ascent - v_metrics.descent).ceil() as u32;
-            let glyphs_width = {
-                let max_x = glyphs
-                    .last()
-                    .map(|g| g.pixel_bounding_box().unwrap().max.x)
-                    .unwrap();
-                max_x as u32
-            };
+            // Glyphs without bounding box (e.g. spaces) are skipped,
+            // and the advance width is used if none has it.
+            let max_x = glyphs
+                .iter()
+                .rev()
+                .find_map(|g| g.pixel_bounding_box().map(|bb| bb.max.x))
+                .or_else(|| {
+                    glyphs.last().map(|g| {
+                        let advance = g.unpositioned().h_metrics().advance_width;
+                        (g.position().x + advance).ceil() as i32
+                    })
+                })
+                .unwrap_or(0);
+            let glyphs_width = max_x.max(0) as u32;
             (glyphs_width, glyphs_height)
         }
     }
 
+    /// Layout glyphs, each taken from font or the first fallback having it.
+    /// With hint, advance widths are snapped to integer pixels (crisper at small scales).
+    fn layout_glyphs<'f>(
+        font: &Font<'f>,
+        fallbacks: &[Font<'f>],
+        text: &str,
+        scale: Scale,
+        hint: bool,
+    ) -> Vec<PositionedGlyph<'f>> {
+        let snap = |v: f32| if hint { v.round() } else { v };
+        let ascent = snap(font.v_metrics(scale).ascent);
+        let mut glyphs = Vec::new();
+        let mut x = 0.0f32;
+        let mut last: Option<(usize, GlyphId)> = None;
+
+        for c in text.chars() {
+            // 0: font, 1..: fallbacks
+            let idx = if font.glyph(c).id().0 > 0 {
+                0
+            } else {
+                fallbacks
+                    .iter()
+                    .position(|f| f.glyph(c).id().0 > 0)
+                    .map_or(0, |i| i + 1)
+            };
+            let f = if idx == 0 { font } else { &fallbacks[idx - 1] };
+            let glyph = f.glyph(c).scaled(scale);
+            if let Some((last_idx, last_id)) = last {
+                if last_idx == idx {
+                    x += snap(f.pair_kerning(scale, last_id, glyph.id()));
+                }
+            }
+            last = Some((idx, glyph.id()));
+            let advance = snap(glyph.h_metrics().advance_width);
+            glyphs.push(glyph.positioned(point(x, ascent)));
+            x += advance;
+        }
+        glyphs
+    }
+
+    /// Draw laid out glyphs at (x, y) (same manner as draw_text_mut).
+    fn draw_glyphs_mut(
+        img: &mut RgbaImage,
+        col: Rgba<u8>,
+        x: u32,
+        y: u32,
+        glyphs: &[PositionedGlyph],
+    ) {
+        let (w, h) = (img.width() as i32, img.height() as i32);
+
+        for glyph in glyphs {
+            if let Some(bb) = glyph.pixel_bounding_box() {
+                glyph.draw(|gx, gy, gv| {
+                    let gx = gx as i32 + bb.min.x + x as i32;
+                    let gy = gy as i32 + bb.min.y + y as i32;
+                    if gx >= 0 && gx < w && gy >= 0 && gy < h {
+                        let pixel = *img.get_pixel(gx as u32, gy as u32);
+                        img.put_pixel(gx as u32, gy as u32, weighted_sum(pixel, col, 1.0 - gv, gv));
+                    }
+                });
+            }
+        }
+    }
+
     /// Get image for text.
-    fn get_text_img(
-        font: &Font,
+    fn get_text_img<'f>(
+        font: &Font<'f>,
+        fallbacks: &[Font<'f>],
         text: &str,
         scale: Scale,
         col: image::Rgba<u8>,
+        bg: image::Rgba<u8>,
+        hint: bool,
     ) -> Option<RgbaImage> {
         if text.is_empty() {
             None
@@ -459,7 +1782,7 @@ impl State<'_> {
             let t_h: u32;
 
             // Title text image
-            (t_w, t_h) = Self::calc_text_size(font, text, scale);
+            (t_w, t_h) = Self::calc_text_size(font, fallbacks, text, scale, hint);
 
             let w = if t_w <= DISP_AREA_WIDTH {
                 t_w
@@ -467,188 +1790,602 @@ impl State<'_> {
                 t_w + 20 + DISP_AREA_WIDTH
             };
             let mut img = RgbaImage::new(w, t_h);
-            draw_filled_rect_mut(&mut img, Rect::at(0, 0).of_size(w, t_h), COLOR_BLACK);
-            draw_text_mut(&mut img, col, 0, 0, scale, font, text);
+            draw_filled_rect_mut(&mut img, Rect::at(0, 0).of_size(w, t_h), bg);
+            let glyphs = Self::layout_glyphs(font, fallbacks, text, scale, hint);
+            Self::draw_glyphs_mut(&mut img, col, 0, 0, &glyphs);
             if t_w > DISP_AREA_WIDTH {
-                draw_text_mut(&mut img, col, t_w + 20, 0, scale, font, text);
+                Self::draw_glyphs_mut(&mut img, col, t_w + 20, 0, &glyphs);
             }
             Some(img)
         }
     }
 
+    /// Classify error of Volumio API call.
+    pub fn classify_api_error(e: &reqwest::Error) -> ApiError {
+        if e.is_timeout() {
+            ApiError::Timeout
+        } else if e.is_connect() {
+            ApiError::ConnectionRefused
+        } else if let Some(status) = e.status() {
+            ApiError::HttpError(status.as_u16())
+        } else {
+            ApiError::ParseError(e.to_string())
+        }
+    }
+
     /// Get Information from Volumio.
     pub fn update_state(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         // get MDP status
-        if let Ok(res) = reqwest::blocking::get(format!("{MDP_BASE_URL}{GET_STATE_API}")) {
-            if let Ok(info) = res.json::<Info>() {
-                let baseimg = &mut self.baseimg;
-                let pre_info = &mut self.pre_info;
+        self.api_connected = false;
+        let result = self
+            .http_client
+            .get(format!("{MDP_BASE_URL}{GET_STATE_API}"))
+            .send()
+            .and_then(|res| res.error_for_status())
+            .and_then(|res| res.json::<Info>());
+        let api_error = result.as_ref().err().map(Self::classify_api_error);
+        if api_error != self.api_error {
+            match &api_error {
+                // Volumio is still booting. Not worth logging.
+                Some(ApiError::ConnectionRefused) | None => {}
+                Some(ApiError::Timeout) => eprintln!("Warning: Volumio API timed out"),
+                Some(ApiError::HttpError(code)) => {
+                    eprintln!("Warning: Volumio API returned HTTP {code}")
+                }
+                Some(ApiError::ParseError(e)) => eprintln!("Failed to parse Volumio state: {e}"),
+            }
+            self.api_error = api_error;
+        }
 
-                if !info.status.eq(&pre_info.status) {
-                    draw_filled_rect_mut(
-                        baseimg,
-                        Rect::at(DISP_AREA_MARGIN_X, DISP_AREA_MARGIN_Y)
-                            .of_size(DISP_AREA_WIDTH, DISP_AREA_HEIGHT),
-                        COLOR_BLACK,
-                    );
+        if let Ok(info) = result {
+            self.api_connected = true;
+            self.api_last_success = Instant::now();
 
-                    self.mpd_status_change = true;
-                }
+            let baseimg = &mut self.baseimg;
+            let pre_info = &self.pre_info;
 
-                // Title changed
-                if !info.title.eq(&pre_info.title) {
-                    self.title_x = 0;
-                    self.title_txt_img = Self::get_text_img(
-                        &self.font_i,
-                        &info.title,
-                        self.scale_l,
-                        COLOR_LIGHTBLUE,
-                    );
-                    draw_filled_rect_mut(
-                        baseimg,
-                        Rect::at(TITLE_INFO_X, TITLE_INFO_Y)
-                            .of_size(TITLE_INFO_WIDTH, TITLE_INFO_HEIGHT),
-                        COLOR_BLACK,
-                    );
-                }
-                // Album changed
-                if !info.album.eq(&pre_info.album) {
-                    self.album_x = 0;
-                    self.album_txt_img =
-                        Self::get_text_img(&self.font_i, &info.album, self.scale_m, COLOR_WHITE);
-                    draw_filled_rect_mut(
-                        baseimg,
-                        Rect::at(ALBUM_INFO_X, ALBUM_INFO_Y)
-                            .of_size(ALBUM_INFO_WIDTH, ALBUM_INFO_HEIGHT),
-                        COLOR_BLACK,
-                    );
-                }
-                // Artist changed
-                if !info.artist.eq(&pre_info.artist) {
-                    self.artist_x = 0;
-                    self.artist_txt_img =
-                        Self::get_text_img(&self.font_i, &info.artist, self.scale_m, COLOR_WHITE);
-                    draw_filled_rect_mut(
-                        baseimg,
-                        Rect::at(ARTIST_INFO_X, ARTIST_INFO_Y)
-                            .of_size(ARTIST_INFO_WIDTH, ARTIST_INFO_HEIGHT),
-                        COLOR_BLACK,
-                    );
-                }
-                // Albumart changed
-                if !info.albumart.eq(&pre_info.albumart) || self.mpd_status_change {
-                    // Thumbnail
-                    let img_bytes = if info.albumart.starts_with("http") {
-                        reqwest::blocking::get(info.albumart.to_string())?.bytes()?
-                    } else {
-                        reqwest::blocking::get(format!("{}{}", MDP_BASE_URL, &info.albumart))?
-                            .bytes()?
-                    };
-                    let img = image::load_from_memory(&img_bytes).unwrap();
+            if !info.status.eq(&pre_info.status) {
+                draw_filled_rect_mut(
+                    baseimg,
+                    Rect::at(DISP_AREA_MARGIN_X, DISP_AREA_MARGIN_Y)
+                        .of_size(DISP_AREA_WIDTH, DISP_AREA_HEIGHT),
+                    COLOR_BLACK,
+                );
+                draw_filled_rect_mut(
+                    baseimg,
+                    Rect::at(TITLE_INFO_X, TITLE_INFO_Y)
+                        .of_size(TITLE_INFO_WIDTH, TITLE_INFO_HEIGHT),
+                    self.title_bg,
+                );
 
-                    let resized_img = img.resize(THUMB_WIDTH, THUMB_HEIGHT, FilterType::Triangle);
+                self.mpd_status_change = true;
+                self.api_icon_drawn = None;
+                self.snr_drawn = false;
+                self.region_dirty = [true; NUM_REGIONS];
 
-                    let x_of: i32 = if resized_img.width() >= THUMB_WIDTH {
-                        0
-                    } else {
-                        ((THUMB_WIDTH - resized_img.width()) / 2) as i32
-                    };
-                    let y_of: i32 = if resized_img.height() >= THUMB_HEIGHT {
-                        0
-                    } else {
-                        ((THUMB_HEIGHT - resized_img.height()) / 2) as i32
-                    };
-                    imageops::overlay(
-                        baseimg,
-                        &resized_img,
-                        (THUMB_X + x_of) as u32,
-                        (THUMB_Y + y_of) as u32,
-                    );
-                    draw_hollow_rect_mut(
-                        baseimg,
-                        Rect::at(THUMB_X, THUMB_Y).of_size(THUMB_WIDTH, THUMB_HEIGHT),
-                        COLOR_WHITE,
-                    );
-                }
-                // SampleRate/BitDepth/Channels
-                if let Some(sr) = info.samplerate.split_whitespace().next() {
-                    let sr0 = format!("{:.0}", f64::from_str(sr)? * 1000.0);
-                    if let Some(bd) = info.bitdepth.split_whitespace().next() {
-                        let s = format!("{}:{}:{}", sr0, bd, info.channels);
-                        draw_filled_rect_mut(
-                            baseimg,
-                            Rect::at(AUDIO_X, AUDIO_Y).of_size(AUDIO_WIDTH, AUDIO_HEIGHT),
-                            COLOR_BLACK,
-                        );
-                        draw_text_mut(
-                            baseimg,
-                            COLOR_WHITE,
-                            AUDIO_X as u32,
-                            AUDIO_Y as u32,
-                            self.scale_s,
-                            &self.font_n,
-                            &s,
-                        );
-                    }
+                if self.enable_transitions
+                    && ((pre_info.is_paused() && info.is_playing())
+                        || (pre_info.is_playing() && info.is_paused()))
+                {
+                    self.transition_frames = TRANSITION_FRAMES;
                 }
+            }
 
-                // Seek bar
-                let seek_pos = if info.duration > 0 {
-                    SEEK_WIDTH * info.seek / (info.duration * 1000)
-                } else {
-                    0
-                };
-                if (seek_pos != self.seek_pos) || self.mpd_status_change {
-                    draw_filled_rect_mut(
-                        baseimg,
-                        Rect::at(SEEK_X, SEEK_Y).of_size(SEEK_WIDTH, SEEK_HEIGHT),
-                        COLOR_GREY,
-                    );
-                    if seek_pos > 0 {
-                        draw_filled_rect_mut(
-                            baseimg,
-                            Rect::at(SEEK_X, SEEK_Y).of_size(seek_pos, SEEK_HEIGHT),
-                            COLOR_WHITE,
-                        );
-                    }
-                    self.seek_pos = seek_pos;
+            // Genre changed
+            if !info.genre.eq(&pre_info.genre) && !self.genre_rotations.is_empty() {
+                if let Some(tx) = &self.rotation_tx {
+                    // applied by main loop before next frame
+                    let rotation = genre_rotation(&self.genre_rotations, &info.genre);
+                    let _ = tx.send(DisplayCommand::SetRotation(rotation));
                 }
+            }
 
-                // CPU temperature
-                let temp = match fs::read_to_string(CPU_THM_FILE) {
-                    Ok(temp) => {
-                        let n: f32 = temp.trim().parse::<f32>().unwrap() / 1000.0f32;
-                        format!("CPU {n:.1} C")
+            // Title changed
+            if !info.title.eq(&pre_info.title) {
+                // Volumio restarted (title has gone and come back)
+                if info.title.is_empty() {
+                    self.session_title_lost = true;
+                } else if self.session_title_lost {
+                    self.session_title_lost = false;
+                    if self.reset_session_on_stop {
+                        self.session_elapsed_secs = 0;
+                        self.session_elapsed_msec = 0;
                     }
-                    Err(_) => "CPU --.- C".to_string(),
+                }
+                self.title_txt_img = Self::get_text_img(
+                    &self.font_i,
+                    &self.fallback_fonts,
+                    &info.title,
+                    self.scale_l,
+                    self.current_theme.title_color,
+                    self.title_bg,
+                    self.hint_text,
+                );
+                self.title_dir = detect_text_direction(&info.title);
+                self.title_x = Self::scroll_start_x(&self.title_txt_img, self.title_dir);
+                self.region_dirty[Region::Title as usize] = true;
+                draw_filled_rect_mut(
+                    baseimg,
+                    Rect::at(TITLE_INFO_X, TITLE_INFO_Y)
+                        .of_size(TITLE_INFO_WIDTH, TITLE_INFO_HEIGHT),
+                    self.title_bg,
+                );
+            }
+            // Album changed
+            if !info.album.eq(&pre_info.album) {
+                self.album_x = 0;
+                self.region_dirty[Region::Album as usize] = true;
+                self.album_txt_img = Self::get_text_img(
+                    &self.font_i,
+                    &self.fallback_fonts,
+                    &info.album,
+                    self.scale_m,
+                    COLOR_WHITE,
+                    COLOR_BLACK,
+                    self.hint_text,
+                );
+                draw_filled_rect_mut(
+                    baseimg,
+                    Rect::at(ALBUM_INFO_X, ALBUM_INFO_Y)
+                        .of_size(ALBUM_INFO_WIDTH, ALBUM_INFO_HEIGHT),
+                    COLOR_BLACK,
+                );
+            }
+            // Artist changed
+            if !info.artist.eq(&pre_info.artist) {
+                self.artist_x = 0;
+                self.region_dirty[Region::Artist as usize] = true;
+                self.artist_txt_img = Self::get_text_img(
+                    &self.font_i,
+                    &self.fallback_fonts,
+                    &info.artist,
+                    self.scale_m,
+                    COLOR_WHITE,
+                    COLOR_BLACK,
+                    self.hint_text,
+                );
+                draw_filled_rect_mut(
+                    baseimg,
+                    Rect::at(ARTIST_INFO_X, ARTIST_INFO_Y)
+                        .of_size(ARTIST_INFO_WIDTH, ARTIST_INFO_HEIGHT),
+                    COLOR_BLACK,
+                );
+            }
+            // Track changed (fetch lyrics in background)
+            if self.show_lyrics
+                && !info.title.is_empty()
+                && (!info.title.eq(&pre_info.title) || !info.artist.eq(&pre_info.artist))
+            {
+                let key = lyrics_key(&info);
+                if !self.lyrics_cache.contains_key(&key) {
+                    let (tx, rx) = mpsc::channel();
+                    let (artist, title) = (info.artist.clone(), info.title.clone());
+                    let client = self.http_client.clone();
+                    thread::spawn(move || {
+                        let lines = VolumioLyrics::fetch(&client, MDP_BASE_URL, &artist, &title)
+                            .unwrap_or_default();
+                        let _ = tx.send((key, lines));
+                    });
+                    self.lyrics_rx = Some(rx);
+                }
+            }
+            // Albumart changed (fetch in background)
+            if !info.albumart.eq(&pre_info.albumart)
+                || (self.thumb_img.is_none()
+                    && !self.album_art_loading
+                    && !self.last_albumart_error)
+            {
+                let url = if info.albumart.starts_with("http") {
+                    info.albumart.to_string()
+                } else {
+                    format!("{}{}", MDP_BASE_URL, &info.albumart)
                 };
+                let (tx, rx) = mpsc::channel();
+                let client = self.http_client.clone();
+                thread::spawn(move || {
+                    let _ = tx.send(fetch_thumb(&client, &url));
+                });
+                self.albumart_rx = Some(rx);
+                self.album_art_loading = true;
+                self.last_albumart_error = false;
+                self.frame_count = 0;
+            }
+            if self.mpd_status_change {
+                self.thumb_dirty = true;
+            }
+            // Audio quality
+            let s = info.display_quality_string();
+            if !s.is_empty() {
                 draw_filled_rect_mut(
                     baseimg,
-                    Rect::at(CPU_THM_X, CPU_THM_Y).of_size(CPU_THM_WIDTH, CPU_THM_HEIGHT),
+                    Rect::at(AUDIO_X, AUDIO_Y).of_size(AUDIO_WIDTH, AUDIO_HEIGHT),
                     COLOR_BLACK,
                 );
+                // Media type icon
+                let text_x = match icons::icon_for_track_type(&info.track_type) {
+                    Some(icon) => {
+                        icons::draw_icon(baseimg, icon, AUDIO_X, AUDIO_Y, COLOR_WHITE);
+                        AUDIO_X + icons::ICON_SIZE as i32 + 2
+                    }
+                    None => AUDIO_X,
+                };
                 draw_text_mut(
                     baseimg,
                     COLOR_WHITE,
-                    CPU_THM_X as u32,
-                    CPU_THM_Y as u32,
+                    text_x as u32,
+                    AUDIO_Y as u32,
                     self.scale_s,
                     &self.font_n,
-                    &temp,
+                    &s,
                 );
+            }
+
+            let progress_change = info.elapsed_secs() != pre_info.elapsed_secs()
+                || info.duration != pre_info.duration
+                || self.mpd_status_change;
+
+            // Seek bar
+            let seek_pos = if info.duration > 0 {
+                SEEK_WIDTH * info.seek / (info.duration * 1000)
+            } else {
+                0
+            };
+            if (seek_pos != self.seek_pos) || self.mpd_status_change {
+                let DisplayLayout {
+                    seek_y,
+                    seek_height,
+                    seek_radius,
+                    ..
+                } = self.layout;
+                let seek_rect = Rect::at(SEEK_X, seek_y).of_size(SEEK_WIDTH, seek_height);
+                if seek_radius > 0 {
+                    draw_filled_rect_mut(baseimg, seek_rect, COLOR_BLACK);
+                }
+                draw_rounded_rect_mut(baseimg, seek_rect, seek_radius, COLOR_GREY);
+                if seek_pos > 0 {
+                    fill_rounded_hgradient_mut(
+                        baseimg,
+                        Rect::at(SEEK_X, seek_y).of_size(seek_pos, seek_height),
+                        seek_radius,
+                        self.current_theme.seek_bar_color,
+                        COLOR_WHITE,
+                    );
+                    // Round end-cap (kept inside of the bar)
+                    let r = (seek_height / 2) as i32;
+                    let cx = (SEEK_X + seek_pos as i32).min(SEEK_X + SEEK_WIDTH as i32 - r);
+                    draw_filled_circle_mut(baseimg, (cx, seek_y + r), r, COLOR_WHITE);
+                }
+                self.seek_pos = seek_pos;
+            }
+
+            // CPU temperature
+            let temp = match fs::read_to_string(CPU_THM_FILE) {
+                Ok(temp) => {
+                    let n: f32 = temp.trim().parse::<f32>().unwrap() / 1000.0f32;
+                    if self.cpu_temp_history.is_empty()
+                        || self.cpu_temp_pushed.elapsed() >= Duration::from_secs(1)
+                    {
+                        if self.cpu_temp_history.len() == CPU_HISTORY_LEN {
+                            self.cpu_temp_history.pop_front();
+                        }
+                        self.cpu_temp_history.push_back(n);
+                        self.cpu_temp_pushed = Instant::now();
+                    }
+                    format!("{n:4.1} C")
+                }
+                Err(_) => "--.- C".to_string(),
+            };
+            draw_filled_rect_mut(
+                baseimg,
+                Rect::at(CPU_THM_X, CPU_THM_Y).of_size(CPU_THM_WIDTH, CPU_THM_HEIGHT),
+                COLOR_BLACK,
+            );
+            draw_sparkline(
+                baseimg,
+                Rect::at(CPU_THM_X, CPU_SPARK_Y).of_size(CPU_SPARK_WIDTH, CPU_SPARK_HEIGHT),
+                &self.cpu_temp_history,
+                CPU_HISTORY_LEN,
+                COLOR_LIGHTBLUE,
+            );
+            draw_text_mut(
+                baseimg,
+                COLOR_WHITE,
+                CPU_LABEL_X as u32,
+                CPU_THM_Y as u32,
+                self.scale_s,
+                &self.font_n,
+                &temp,
+            );
+
+            // Track progress
+            if progress_change {
+                self.draw_track_progress_text(&info);
+            }
 
-                // backup info
-                *pre_info = info;
-                self.mpd_status_change = false;
+            // backup info (a move: strings of info are not copied)
+            self.pre_info = info;
+            self.mpd_status_change = false;
+
+            self.update_freeze();
+
+            // Queue length for the position indicator
+            if self.pre_info.position.is_some()
+                && self
+                    .queue_fetched
+                    .is_none_or(|t| t.elapsed().as_secs() >= QUEUE_INTERVAL_SEC)
+            {
+                self.fetch_queue();
             }
         }
+
+        if let Some(sec) = self.api_error.as_ref().and_then(ApiError::retry_secs) {
+            self.info_interval_sec = sec;
+        } else if self.adaptive_polling {
+            self.info_interval_sec = if !self.api_connected {
+                INFO_RETRY_INTERVAL_SEC
+            } else if self.pre_info.is_playing() {
+                INFO_PLAY_INTERVAL_SEC
+            } else {
+                INFO_IDLE_INTERVAL_SEC
+            };
+        } else {
+            self.info_interval_sec = INFO_INTERVAL_SEC;
+        }
         Ok(())
     }
 
+    /// Volumio is stuck in play with seek not advancing.
+    fn detect_status_freeze(
+        &self,
+        last_seek: u32,
+        current_seek: u32,
+        last_update: Instant,
+    ) -> bool {
+        self.pre_info.is_playing()
+            && last_update.elapsed().as_secs() > FREEZE_SEC
+            && current_seek == last_seek
+    }
+
+    /// Check freeze, and show warning (and restart playback) when detected.
+    fn update_freeze(&mut self) {
+        let seek = self.pre_info.seek;
+        let freeze = self.detect_status_freeze(self.freeze_seek, seek, self.freeze_seek_t);
+        if seek != self.freeze_seek || !self.pre_info.is_playing() {
+            self.freeze_seek = seek;
+            self.freeze_seek_t = Instant::now();
+        }
+
+        if freeze {
+            // Exclamation mark (redrawn as progress text clears it)
+            let (x, y) = (FREEZE_ICON_X, self.layout.progress_y);
+            let (w, h) = (FREEZE_ICON_WIDTH, FREEZE_ICON_HEIGHT);
+            draw_filled_rect_mut(
+                &mut self.baseimg,
+                Rect::at(x, y).of_size(w, h - w - 2),
+                COLOR_ORANGE,
+            );
+            draw_filled_rect_mut(
+                &mut self.baseimg,
+                Rect::at(x, y + (h - w) as i32).of_size(w, w),
+                COLOR_ORANGE,
+            );
+
+            if !self.freeze_detected && self.auto_recover_freeze {
+                eprintln!("Warning: Volumio status freeze detected, restart playback");
+                let client = self.http_client.clone();
+                thread::spawn(move || {
+                    for cmd in ["stop", "play"] {
+                        let url = format!("{MDP_BASE_URL}{COMMANDS_API}{cmd}");
+                        if client.post(url).send().is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        } else if self.freeze_detected {
+            draw_filled_rect_mut(
+                &mut self.baseimg,
+                Rect::at(FREEZE_ICON_X, self.layout.progress_y)
+                    .of_size(FREEZE_ICON_WIDTH, FREEZE_ICON_HEIGHT),
+                COLOR_BLACK,
+            );
+        }
+        self.freeze_detected = freeze;
+    }
+
+    pub fn to_snapshot(&self) -> StateSnapshot {
+        StateSnapshot {
+            pre_info: self.pre_info.clone(),
+            seek_pos: self.seek_pos,
+            title_x: self.title_x,
+            album_x: self.album_x,
+            artist_x: self.artist_x,
+        }
+    }
+
+    /// Restore info and scroll positions (texts are rendered again).
+    pub fn apply_snapshot(&mut self, snap: StateSnapshot) {
+        let info = snap.pre_info;
+        self.title_txt_img = Self::get_text_img(
+            &self.font_i,
+            &self.fallback_fonts,
+            &info.title,
+            self.scale_l,
+            self.current_theme.title_color,
+            self.title_bg,
+            self.hint_text,
+        );
+        self.album_txt_img = Self::get_text_img(
+            &self.font_i,
+            &self.fallback_fonts,
+            &info.album,
+            self.scale_m,
+            COLOR_WHITE,
+            COLOR_BLACK,
+            self.hint_text,
+        );
+        self.artist_txt_img = Self::get_text_img(
+            &self.font_i,
+            &self.fallback_fonts,
+            &info.artist,
+            self.scale_m,
+            COLOR_WHITE,
+            COLOR_BLACK,
+            self.hint_text,
+        );
+        self.title_dir = detect_text_direction(&info.title);
+        self.pre_info = info;
+        self.seek_pos = snap.seek_pos;
+        self.title_x = snap.title_x;
+        self.album_x = snap.album_x;
+        self.artist_x = snap.artist_x;
+        self.mpd_status_change = true;
+        self.region_dirty = [true; NUM_REGIONS];
+    }
+
+    /// Change mode, cleaning up for the old mode.
+    pub fn transition_to(&mut self, new_mode: AppMode) {
+        if self.mode.is_music() && !new_mode.is_music() {
+            // Animations of music screen
+            self.transition_frames = 0;
+            self.lyrics_shown = None;
+            self.lyrics_txt_img = None;
+        }
+        if self.mode.draw_mode() != new_mode.draw_mode() {
+            // Parts drawn only when changed
+            self.thumb_dirty = true;
+            self.snr_drawn = false;
+            self.api_icon_drawn = None;
+            self.region_dirty = [true; NUM_REGIONS];
+            self.last_clock_second = u32::MAX;
+        }
+        self.mode = new_mode;
+    }
+
+    /// Clear screen and force to redraw all.
+    pub fn clear(&mut self) {
+        draw_filled_rect_mut(
+            &mut self.baseimg,
+            Rect::at(0, 0).of_size(DISP_WIDTH, DISP_HEIGHT),
+            COLOR_BLACK,
+        );
+        self.pre_info = Info::default();
+        self.title_txt_img = None;
+        self.album_txt_img = None;
+        self.artist_txt_img = None;
+        self.seek_pos = 0;
+        self.mpd_status_change = true;
+        self.api_icon_drawn = None;
+        self.snr_drawn = false;
+        self.thumb_dirty = true;
+        self.region_dirty = [true; NUM_REGIONS];
+        self.last_clock_second = u32::MAX;
+    }
+
+    /// Draw Volumio API connectivity icon at top-right corner.
+    /// Icon differs by error class (None: connected).
+    fn draw_net_icon(img: &mut RgbaImage, error: Option<&ApiError>, fresh: bool) {
+        let col = if fresh { COLOR_GREEN } else { COLOR_GREY };
+        let (x, y) = (NET_ICON_X as f32, NET_ICON_Y as f32);
+        let segments: &[Segment] = match error {
+            // Three nested arcs
+            None => &[
+                ((0.0, 2.0), (2.0, 0.0)),
+                ((2.0, 0.0), (5.0, 0.0)),
+                ((5.0, 0.0), (7.0, 2.0)),
+                ((2.0, 3.0), (3.0, 2.0)),
+                ((3.0, 2.0), (4.0, 2.0)),
+                ((4.0, 2.0), (5.0, 3.0)),
+                ((3.0, 4.0), (4.0, 4.0)),
+            ],
+            // Cross
+            Some(ApiError::ConnectionRefused) => {
+                &[((0.0, 0.0), (7.0, 4.0)), ((0.0, 4.0), (7.0, 0.0))]
+            }
+            // Hourglass
+            Some(ApiError::Timeout) => &[
+                ((1.0, 0.0), (6.0, 0.0)),
+                ((1.0, 4.0), (6.0, 4.0)),
+                ((1.0, 0.0), (6.0, 4.0)),
+                ((1.0, 4.0), (6.0, 0.0)),
+            ],
+            // Exclamation mark
+            Some(ApiError::HttpError(_)) => &[((3.0, 0.0), (3.0, 2.0)), ((3.0, 4.0), (3.0, 4.0))],
+            // Question mark
+            Some(ApiError::ParseError(_)) => &[
+                ((2.0, 0.0), (5.0, 0.0)),
+                ((5.0, 0.0), (5.0, 1.0)),
+                ((5.0, 1.0), (3.0, 2.0)),
+                ((3.0, 4.0), (3.0, 4.0)),
+            ],
+        };
+
+        draw_filled_rect_mut(
+            img,
+            Rect::at(NET_ICON_X, NET_ICON_Y).of_size(NET_ICON_WIDTH, NET_ICON_HEIGHT),
+            COLOR_BLACK,
+        );
+        for ((x0, y0), (x1, y1)) in segments {
+            draw_line_segment_mut(img, (x + x0, y + y0), (x + x1, y + y1), col);
+        }
+    }
+
+    /// Draw API connectivity icon if changed.
+    fn update_net_icon(&mut self) {
+        let fresh = self.api_last_success.elapsed().as_secs() <= NET_ICON_STALE_SEC;
+        let error = if self.api_connected {
+            None
+        } else {
+            // Not polled yet: drawn as refused
+            Some(
+                self.api_error
+                    .clone()
+                    .unwrap_or(ApiError::ConnectionRefused),
+            )
+        };
+        if self.api_icon_drawn.as_ref() != Some(&(error.clone(), fresh)) {
+            Self::draw_net_icon(&mut self.baseimg, error.as_ref(), fresh);
+            self.api_icon_drawn = Some((error, fresh));
+        }
+    }
+
+    /// Draw track progress like "2:34 / 5:12" above the seek bar.
+    pub fn draw_track_progress_text(&mut self, info: &Info) {
+        let text = if info.duration > 0 {
+            format!(
+                "{} / {}",
+                format_time(info.elapsed_secs()),
+                format_time(info.duration)
+            )
+        } else {
+            // Stream
+            format_time(info.elapsed_secs())
+        };
+        draw_filled_rect_mut(
+            &mut self.baseimg,
+            Rect::at(PROGRESS_X, self.layout.progress_y).of_size(PROGRESS_WIDTH, PROGRESS_HEIGHT),
+            COLOR_BLACK,
+        );
+        draw_text_mut(
+            &mut self.baseimg,
+            COLOR_WHITE,
+            PROGRESS_X as u32,
+            self.layout.progress_y as u32,
+            self.scale_s,
+            &self.font_n,
+            &text,
+        );
+    }
+
     /// Update image in clock mode.
-    pub fn draw_clock(&mut self) {
-        let baseimg = &mut self.baseimg;
+    /// Returns false if not redrawn (the second has not changed).
+    pub fn draw_clock(&mut self) -> bool {
         let dt = Local::now();
+        if dt.second() == self.last_clock_second {
+            return false;
+        }
+        self.last_clock_second = dt.second();
+        let baseimg = &mut self.baseimg;
 
         draw_filled_rect_mut(
             baseimg,
@@ -674,12 +2411,535 @@ impl State<'_> {
             &self.font_n,
             &dt.format("%H:%M").to_string(),
         );
-    }
+        if self.session_elapsed_secs > 0 {
+            draw_text_mut(
+                baseimg,
+                COLOR_WHITE,
+                SESSION_INFO_X as u32,
+                SESSION_INFO_Y as u32,
+                self.scale_m,
+                &self.font_n,
+                &format!("Session: {}", format_hm(self.session_elapsed_secs)),
+            );
+        }
 
-    /// Update image in playing mode.
-    pub fn draw_music_info(&mut self, sp: &mut Option<&mut SpInfo>) {
-        let mut restart_scroll = true;
-        let baseimg = &mut self.baseimg;
+        if self.show_qr_code {
+            // IP address may change (e.g. DHCP after boot)
+            if self.qr_url.is_none() || dt.second() == 0 {
+                self.qr_url = local_ip_address().map(|ip| format!("http://{ip}"));
+            }
+            if let Some(url) = &self.qr_url {
+                draw_qr_code(
+                    baseimg,
+                    url,
+                    QR_X,
+                    QR_Y,
+                    QR_MODULE_SIZE,
+                    COLOR_BLACK,
+                    COLOR_WHITE,
+                );
+            }
+        }
+
+        // whole area has been cleared
+        self.api_icon_drawn = None;
+        self.update_net_icon();
+        true
+    }
+
+    /// Update image in stop mode.
+    /// The image is greyed out after conversion, and then stop_overlay is overlaid.
+    pub fn draw_stop(&mut self) {
+        self.draw_thumb();
+
+        let overlay = &mut self.stop_overlay;
+        for p in overlay.pixels_mut() {
+            *p = COLOR_TRANSPARENT;
+        }
+
+        let text = "STOPPED";
+        let (w, h) = Self::calc_text_size(
+            &self.font_i,
+            &self.fallback_fonts,
+            text,
+            self.scale_xl,
+            self.hint_text,
+        );
+        draw_text_mut(
+            overlay,
+            COLOR_RED,
+            DISP_WIDTH.saturating_sub(w) / 2,
+            DISP_HEIGHT.saturating_sub(h) / 2,
+            self.scale_xl,
+            &self.font_i,
+            text,
+        );
+        draw_text_mut(
+            overlay,
+            COLOR_WHITE,
+            STOP_CLOCK_X as u32,
+            STOP_CLOCK_Y as u32,
+            self.scale_m,
+            &self.font_n,
+            &Local::now().format("%H:%M").to_string(),
+        );
+    }
+
+    /// Update image for startup splash.
+    pub fn draw_splash(&mut self) {
+        let baseimg = &mut self.baseimg;
+        draw_filled_rect_mut(
+            baseimg,
+            Rect::at(0, 0).of_size(DISP_WIDTH, DISP_HEIGHT),
+            COLOR_BLACK,
+        );
+        draw_text_mut(
+            baseimg,
+            COLOR_LIGHTBLUE,
+            DATE_INFO_X as u32,
+            TIME_INFO_Y as u32,
+            self.scale_l,
+            &self.font_i,
+            "st7789volumio",
+        );
+        draw_text_mut(
+            baseimg,
+            COLOR_WHITE,
+            DATE_INFO_X as u32,
+            SESSION_INFO_Y as u32,
+            self.scale_s,
+            &self.font_n,
+            &format!("{VERSION} ({BUILD_DATE})"),
+        );
+    }
+
+    /// Count up playtime in the session.
+    pub fn count_session(&mut self, msec: u64) {
+        let total = self.session_elapsed_msec + msec;
+        self.session_elapsed_secs += total / 1000;
+        self.session_elapsed_msec = total % 1000;
+    }
+
+    /// Receive album art fetched in background.
+    fn receive_albumart(&mut self) {
+        if let Some(rx) = &self.albumart_rx {
+            match rx.try_recv() {
+                Ok(thumb) => {
+                    self.thumb_img = None;
+                    self.thumb_blur_img = None;
+                    self.title_bg = COLOR_BLACK;
+                    self.current_theme = DisplayTheme::default();
+                    self.seek_pos = 0; // redraw seek bar in the theme
+                    if let Some(mut thumb) = thumb {
+                        // Palette is kept while album art URL is the same
+                        if self.palette_url != self.pre_info.albumart {
+                            self.palette = extract_dominant_colors(&thumb, PALETTE_K);
+                            self.palette_url = self.pre_info.albumart.clone();
+                        }
+                        if let Some(&dominant) = self.palette.first() {
+                            // 50% over black
+                            self.title_bg = weighted_sum(dominant, COLOR_BLACK, 0.5, 0.5);
+                        }
+                        draw_album_art_palette(&mut thumb, &self.palette);
+                        if self.adaptive_theme {
+                            self.current_theme = select_theme_from_palette(&self.palette);
+                            self.title_bg = self.current_theme.background_color;
+                        }
+                        // Cache blurred one not to blur every time paused
+                        let mut thumb_blur = imageops::blur(&thumb, THUMB_BLUR_SIGMA);
+                        for img in [&mut thumb, &mut thumb_blur] {
+                            draw_hollow_rect_mut(
+                                img,
+                                Rect::at(0, 0).of_size(THUMB_WIDTH, THUMB_HEIGHT),
+                                COLOR_WHITE,
+                            );
+                        }
+                        self.thumb_img = Some(thumb);
+                        self.thumb_blur_img = Some(thumb_blur);
+                        if self.zoom_album_art {
+                            self.zoom_frames = ZOOM_FRAMES;
+                        }
+                    } else {
+                        self.last_albumart_error = true;
+                        let portrait = self.draw_artist_portrait();
+                        self.thumb_blur_img = Some(imageops::blur(&portrait, THUMB_BLUR_SIGMA));
+                        self.thumb_img = Some(portrait);
+                    }
+                    self.albumart_rx = None;
+                    self.album_art_loading = false;
+                    self.frame_count = 0;
+                    self.thumb_dirty = true;
+                    self.redraw_title_bg();
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => {
+                    self.albumart_rx = None;
+                    self.album_art_loading = false;
+                }
+            }
+        }
+    }
+
+    /// Placeholder of album art: music note and initial of the artist on grey gradient.
+    fn draw_artist_portrait(&self) -> RgbaImage {
+        let mut img = RgbaImage::new(THUMB_WIDTH, THUMB_HEIGHT);
+        fill_hgradient_mut(
+            &mut img,
+            Rect::at(0, 0).of_size(THUMB_WIDTH, THUMB_HEIGHT),
+            COLOR_GREY,
+            COLOR_PLACEHOLDER_DARK,
+        );
+
+        // Music note (eighth note) in the upper half
+        let (cx, cy) = (THUMB_WIDTH as i32 / 2, THUMB_HEIGHT as i32 / 3);
+        draw_filled_circle_mut(&mut img, (cx - 4, cy + 12), 6, COLOR_WHITE);
+        for dx in 1..=2 {
+            let x = (cx + dx) as f32;
+            draw_line_segment_mut(
+                &mut img,
+                (x, (cy + 12) as f32),
+                (x, (cy - 16) as f32),
+                COLOR_WHITE,
+            );
+        }
+        for dy in 0..3 {
+            let y = (cy - 16 + dy) as f32;
+            draw_line_segment_mut(
+                &mut img,
+                ((cx + 2) as f32, y),
+                ((cx + 12) as f32, y + 10.0),
+                COLOR_WHITE,
+            );
+        }
+
+        // Initial of the artist in the lower half
+        if let Some(c) = self.pre_info.artist.chars().next() {
+            let initial: String = c.to_uppercase().collect();
+            let (t_w, _) = Self::calc_text_size(
+                &self.font_i,
+                &self.fallback_fonts,
+                &initial,
+                self.scale_xl,
+                self.hint_text,
+            );
+            let glyphs = Self::layout_glyphs(
+                &self.font_i,
+                &self.fallback_fonts,
+                &initial,
+                self.scale_xl,
+                self.hint_text,
+            );
+            let x = THUMB_WIDTH.saturating_sub(t_w) / 2;
+            Self::draw_glyphs_mut(&mut img, COLOR_WHITE, x, THUMB_HEIGHT / 2, &glyphs);
+        }
+        draw_hollow_rect_mut(
+            &mut img,
+            Rect::at(0, 0).of_size(THUMB_WIDTH, THUMB_HEIGHT),
+            COLOR_WHITE,
+        );
+        img
+    }
+
+    /// Source rectangle (x, y, w, h) of album art zoom-out for this frame.
+    /// Goes from album art to full screen. None if not zooming.
+    pub fn next_zoom_rect(&mut self) -> Option<(u32, u32, u32, u32)> {
+        if self.zoom_frames == 0 {
+            return None;
+        }
+        self.zoom_frames -= 1;
+        self.zoom = 1.0 - self.zoom_frames as f32 / ZOOM_FRAMES as f32;
+        let lerp =
+            |from: u32, to: u32| (from as f32 + (to as f32 - from as f32) * self.zoom) as u32;
+        Some((
+            lerp(THUMB_X as u32, 0),
+            lerp(THUMB_Y as u32, 0),
+            lerp(THUMB_WIDTH, DISP_WIDTH),
+            lerp(THUMB_HEIGHT, DISP_HEIGHT),
+        ))
+    }
+
+    /// Send player command to Volumio in background.
+    pub fn send_player_command(&self, cmd: &'static str) {
+        let client = self.http_client.clone();
+        thread::spawn(move || {
+            let url = format!("{MDP_BASE_URL}{COMMANDS_API}{cmd}");
+            if let Err(e) = client.get(url).send() {
+                eprintln!("Warning: failed to send {cmd} to Volumio: {e}");
+            }
+        });
+    }
+
+    /// Scroll start of text image (right to left text starts from the end).
+    fn scroll_start_x(img: &Option<RgbaImage>, dir: TextDirection) -> u32 {
+        match (img, dir) {
+            (Some(img), TextDirection::Rtl) => img.width().saturating_sub(DISP_AREA_WIDTH),
+            _ => 0,
+        }
+    }
+
+    /// Redraw title with current background color.
+    fn redraw_title_bg(&mut self) {
+        self.title_txt_img = Self::get_text_img(
+            &self.font_i,
+            &self.fallback_fonts,
+            &self.pre_info.title,
+            self.scale_l,
+            self.current_theme.title_color,
+            self.title_bg,
+            self.hint_text,
+        );
+        self.title_x = Self::scroll_start_x(&self.title_txt_img, self.title_dir);
+        self.region_dirty[Region::Title as usize] = true;
+        draw_filled_rect_mut(
+            &mut self.baseimg,
+            Rect::at(TITLE_INFO_X, TITLE_INFO_Y).of_size(TITLE_INFO_WIDTH, TITLE_INFO_HEIGHT),
+            self.title_bg,
+        );
+    }
+
+    /// Draw album art thumbnail, or placeholder while loading.
+    fn draw_thumb(&mut self) {
+        self.receive_albumart();
+
+        let baseimg = &mut self.baseimg;
+        if self.album_art_loading {
+            // Pulsing border
+            let stroke = 1 + ((self.frame_count as f64 * 0.1).sin().abs() * 2.0) as u32;
+            self.frame_count += 1;
+
+            draw_filled_rect_mut(
+                baseimg,
+                Rect::at(THUMB_X, THUMB_Y).of_size(THUMB_WIDTH, THUMB_HEIGHT),
+                COLOR_GREY,
+            );
+            for k in 0..stroke {
+                draw_hollow_rect_mut(
+                    baseimg,
+                    Rect::at(THUMB_X + k as i32, THUMB_Y + k as i32)
+                        .of_size(THUMB_WIDTH - k * 2, THUMB_HEIGHT - k * 2),
+                    COLOR_WHITE,
+                );
+            }
+            self.thumb_dirty = true;
+        } else if self.thumb_dirty {
+            let thumb = if self.pre_info.is_paused() {
+                &self.thumb_blur_img
+            } else {
+                &self.thumb_img
+            };
+            match thumb {
+                Some(thumb) => imageops::overlay(baseimg, thumb, THUMB_X as u32, THUMB_Y as u32),
+                None => draw_filled_rect_mut(
+                    baseimg,
+                    Rect::at(THUMB_X, THUMB_Y).of_size(THUMB_WIDTH, THUMB_HEIGHT),
+                    COLOR_BLACK,
+                ),
+            }
+            self.thumb_dirty = false;
+        }
+    }
+
+    /// Draw current lyrics line over album and artist info.
+    fn draw_lyrics(&mut self) {
+        if let Some(rx) = &self.lyrics_rx {
+            match rx.try_recv() {
+                Ok((key, lines)) => {
+                    self.lyrics_cache.insert(key, lines);
+                    self.lyrics_rx = None;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.lyrics_rx = None,
+            }
+        }
+
+        // No timestamp from the plugin, so cycle lines
+        let elapsed = self.pre_info.elapsed_secs();
+        let line = match self.lyrics_cache.get(&lyrics_key(&self.pre_info)) {
+            Some(lines) if !lines.is_empty() && elapsed >= LYRICS_START_SEC => {
+                let idx = ((elapsed - LYRICS_START_SEC) / LYRICS_LINE_SEC) as usize % lines.len();
+                Some((idx, &lines[idx]))
+            }
+            _ => None,
+        };
+
+        if line.map(|(idx, _)| idx) != self.lyrics_shown {
+            self.lyrics_txt_img = line.and_then(|(_, text)| {
+                Self::get_text_img(
+                    &self.font_i,
+                    &self.fallback_fonts,
+                    text,
+                    self.scale_s,
+                    COLOR_WHITE,
+                    COLOR_BLACK,
+                    self.hint_text,
+                )
+            });
+            self.lyrics_shown = line.map(|(idx, _)| idx);
+            if self.lyrics_shown.is_none() {
+                // album and artist info are back from next frame
+                draw_filled_rect_mut(
+                    &mut self.baseimg,
+                    Rect::at(LYRICS_X, LYRICS_Y).of_size(LYRICS_WIDTH, LYRICS_HEIGHT),
+                    COLOR_BLACK,
+                );
+                self.region_dirty[Region::Album as usize] = true;
+                self.region_dirty[Region::Artist as usize] = true;
+            }
+        }
+
+        if self.lyrics_shown.is_some() {
+            draw_filled_rect_mut(
+                &mut self.baseimg,
+                Rect::at(LYRICS_X, LYRICS_Y).of_size(LYRICS_WIDTH, LYRICS_HEIGHT),
+                COLOR_BLACK,
+            );
+            if let Some(img) = &self.lyrics_txt_img {
+                let w = cmp::min(img.width(), LYRICS_WIDTH);
+                let h = cmp::min(img.height(), LYRICS_HEIGHT);
+                let img0 = imageops::crop_imm(img, 0, 0, w, h);
+                imageops::overlay(
+                    &mut self.baseimg,
+                    &img0,
+                    LYRICS_X as u32,
+                    LYRICS_Y as u32 + (LYRICS_HEIGHT - h) / 2,
+                );
+            }
+        }
+    }
+
+    /// Spectrum over the whole display with small album art and title at the top.
+    pub fn draw_fullscreen_spectrum(&mut self, sp: &mut SpInfo) {
+        self.receive_albumart();
+
+        if self.bar_vals.len() != sp.num_bars() {
+            self.bar_vals = vec![0.0f64; sp.num_bars()];
+        }
+        sp.fft(&mut self.bar_vals);
+        sp.reset_on_silence(&mut self.bar_vals);
+
+        let baseimg = &mut self.baseimg;
+        draw_filled_rect_mut(
+            baseimg,
+            Rect::at(0, 0).of_size(DISP_WIDTH, DISP_HEIGHT),
+            COLOR_BLACK,
+        );
+        // 40 bars: 5px + 1px margin
+        let pitch = DISP_WIDTH / self.bar_vals.len() as u32;
+        for (j, bar) in self.bar_vals.iter().enumerate() {
+            // dB + DYNAMIC_RANGE: 90 + GAIN: 10 / DYNAMIC_RANGE
+            let y = (DISP_HEIGHT as f64 * (bar.log10() * 20.0 + 100.0) / 90.0)
+                .clamp(0.0, DISP_HEIGHT as f64) as u32;
+            draw_gradient_bar(
+                baseimg,
+                (pitch * j as u32) as i32,
+                DISP_HEIGHT as i32,
+                pitch - 1,
+                y,
+                DISP_HEIGHT,
+            );
+        }
+
+        if let Some(thumb) = &self.thumb_img {
+            let small = imageops::resize(
+                thumb,
+                FULL_SP_THUMB_SIZE,
+                FULL_SP_THUMB_SIZE,
+                FilterType::Triangle,
+            );
+            imageops::overlay(baseimg, &small, 0, 0);
+        }
+        if let Some(title_txt_img) = &self.title_txt_img {
+            let x = FULL_SP_THUMB_SIZE + 4;
+            let w = cmp::min(title_txt_img.width(), DISP_WIDTH - x);
+            let img0 = imageops::crop_imm(title_txt_img, 0, 0, w, title_txt_img.height());
+            imageops::overlay(baseimg, &img0, x, 0);
+        }
+    }
+
+    /// Get number of tracks in the queue.
+    pub fn fetch_queue(&mut self) {
+        self.queue_fetched = Some(Instant::now());
+        let queue = self
+            .http_client
+            .get(format!("{MDP_BASE_URL}{QUEUE_API}"))
+            .send()
+            .and_then(|res| res.json::<serde_json::Value>());
+        if let Ok(queue) = queue {
+            self.queue_length = queue["queue"].as_array().map_or(0, |q| q.len() as u32);
+        }
+    }
+
+    /// Draw "3/12" at the right end of the album line.
+    /// Redrawn when the position changes or the album text has overwritten it.
+    fn draw_queue_position(&mut self, album_redrawn: bool) {
+        let queue = match self.pre_info.position {
+            Some(position) if self.queue_length > 0 => Some((position + 1, self.queue_length)),
+            _ => None,
+        };
+        if queue == self.queue_drawn && !album_redrawn {
+            return;
+        }
+        if queue.is_none() && self.queue_drawn.is_some() {
+            // hidden: restore album text from next frame
+            draw_filled_rect_mut(
+                &mut self.baseimg,
+                Rect::at(QUEUE_INFO_X, QUEUE_INFO_Y).of_size(QUEUE_INFO_WIDTH, QUEUE_INFO_HEIGHT),
+                COLOR_BLACK,
+            );
+            self.region_dirty[Region::Album as usize] = true;
+        }
+        if let Some((position, length)) = queue {
+            draw_filled_rect_mut(
+                &mut self.baseimg,
+                Rect::at(QUEUE_INFO_X, QUEUE_INFO_Y).of_size(QUEUE_INFO_WIDTH, QUEUE_INFO_HEIGHT),
+                COLOR_BLACK,
+            );
+            draw_text_mut(
+                &mut self.baseimg,
+                COLOR_WHITE,
+                QUEUE_INFO_X as u32,
+                QUEUE_INFO_Y as u32,
+                self.scale_s,
+                &self.font_n,
+                &format!("{position}/{length}"),
+            );
+        }
+        self.queue_drawn = queue;
+    }
+
+    /// Draw border around display area, getting thinner every frame.
+    fn draw_transition(&mut self) {
+        if self.transition_frames == 0 {
+            return;
+        }
+        self.transition_frames -= 1;
+        let stroke =
+            (self.transition_frames as u32 * TRANSITION_STROKE).div_ceil(TRANSITION_FRAMES as u32);
+        for k in 0..TRANSITION_STROKE {
+            draw_hollow_rect_mut(
+                &mut self.baseimg,
+                Rect::at(
+                    DISP_AREA_MARGIN_X - 1 - k as i32,
+                    DISP_AREA_MARGIN_Y - 1 - k as i32,
+                )
+                .of_size(DISP_AREA_WIDTH + 2 + k * 2, DISP_AREA_HEIGHT + 2 + k * 2),
+                if k < stroke { COLOR_WHITE } else { COLOR_BLACK },
+            );
+        }
+    }
+
+    /// Update image in playing mode.
+    pub fn draw_music_info(&mut self, sp: &mut Option<&mut SpInfo>) {
+        self.update_net_icon();
+        self.draw_thumb();
+
+        let mut restart_scroll = true;
+        let album_redrawn = self.region_dirty[Region::Album as usize]
+            || self
+                .album_txt_img
+                .as_ref()
+                .is_some_and(|img| img.width() > DISP_AREA_WIDTH);
+        let baseimg = &mut self.baseimg;
 
         if let Some(ref mut title_txt_img) = self.title_txt_img {
             let title_x = self.title_x;
@@ -688,12 +2948,18 @@ impl State<'_> {
                 let img0 = imageops::crop(title_txt_img, title_x, 0, DISP_AREA_WIDTH, h0);
                 imageops::overlay(baseimg, &img0, TITLE_INFO_X as u32, TITLE_INFO_Y as u32);
 
-                if title_x < title_txt_img.width() - DISP_AREA_WIDTH {
+                if self.title_dir == TextDirection::Rtl {
+                    if title_x > 0 {
+                        self.title_x = title_x - 1;
+
+                        restart_scroll = false;
+                    }
+                } else if title_x < title_txt_img.width() - DISP_AREA_WIDTH {
                     self.title_x = title_x + 1;
 
                     restart_scroll = false;
                 }
-            } else {
+            } else if self.region_dirty[Region::Title as usize] {
                 imageops::overlay(
                     baseimg,
                     title_txt_img,
@@ -715,7 +2981,7 @@ impl State<'_> {
 
                     restart_scroll = false;
                 }
-            } else {
+            } else if self.region_dirty[Region::Album as usize] {
                 imageops::overlay(
                     baseimg,
                     album_txt_img,
@@ -737,7 +3003,7 @@ impl State<'_> {
 
                     restart_scroll = false;
                 }
-            } else {
+            } else if self.region_dirty[Region::Artist as usize] {
                 imageops::overlay(
                     baseimg,
                     artist_txt_img,
@@ -747,186 +3013,1738 @@ impl State<'_> {
             }
 
             if restart_scroll {
-                self.title_x = 0;
+                self.title_x = Self::scroll_start_x(&self.title_txt_img, self.title_dir);
                 self.album_x = 0;
                 self.artist_x = 0;
             }
         }
+        self.region_dirty = [false; NUM_REGIONS];
 
         // draw_spectrum
         if let Some(ref mut sp_info) = sp {
+            if self.bar_vals.len() != sp_info.num_bars() {
+                self.bar_vals = vec![0.0f64; sp_info.num_bars()];
+            }
             sp_info.fft(&mut self.bar_vals);
+            sp_info.reset_on_silence(&mut self.bar_vals);
+            let (bar_width, bar_margin) = sp_info.bar_size();
 
             draw_filled_rect_mut(
                 baseimg,
                 Rect::at(SP_X, SP_Y).of_size(SP_WIDTH, SP_HEIGHT),
                 COLOR_BLACK,
             );
+            if self.spectrum_grid {
+                // Width actually covered by the bars
+                let bars_w = (bar_width + bar_margin) as u32 * self.bar_vals.len() as u32;
+                draw_spectrum_grid(
+                    baseimg,
+                    SP_X,
+                    SP_Y,
+                    bars_w,
+                    SP_HEIGHT,
+                    sp_info.cut_offs(),
+                    COLOR_SP_GRID,
+                );
+            }
             let mut x = SP_X;
 
-            for i in 0..NUM_BARS {
+            for bar in self.bar_vals.iter() {
                 // dB + DYNAMIC_RANGE: 90 + GAIN: 10 / DYNAMIC_RANGE
-                let mut y: i32 =
-                    (SP_HEIGHT as f64 * (self.bar_vals[i].log10() * 20.0 + 100.0) / 90.0) as i32;
+                let mut y: i32 = (SP_HEIGHT as f64 * (bar.log10() * 20.0 + 100.0) / 90.0) as i32;
                 if y < 0 {
                     y = 0;
                 } else if y > SP_HEIGHT as i32 {
                     y = SP_HEIGHT as i32;
                 }
                 if y > 0 {
-                    draw_filled_rect_mut(
-                        baseimg,
-                        Rect::at(x, (SP_HEIGHT + SP_Y as u32 - y as u32) as i32)
-                            .of_size(SP_BAR_WIDTH as u32, y as u32),
-                        COLOR_SP_BAR,
-                    );
+                    draw_spectrum_bars(baseimg, x, bar_width as u32, y as u32, self.spectrum_style);
                 }
 
-                x += SP_BAR_WIDTH + SP_BAR_MARGIN;
+                x += bar_width + bar_margin;
             }
-        }
-    }
-}
-/// Output Usage
-fn usage() {
-    println!("st7789volumio");
-    println!();
-    println!("Usage: st7789volumio [OPTIONS]");
-    println!();
-    println!("Options:");
-    println!(" -s<spi_bus>      SPI bus (0, 1, 2): Default 0");
-    println!(" -c<cs_pin>       Slave Select pin (0, 1, 2): Default 0");
-    println!("                       spi = 0, cs = 0...GPIO 8, 1...GPIO 7");
-    println!("                       spi = 1, cs = 0...GPIO 18, 1...GPIO 17, 2...GIPI16");
-    println!("                       spi = 2, cs = 0...GPIO 43, 1...GPIO 44, 2...GIPI45");
-    println!(" -d<pin>          GPIO pin number for DC: Default 25");
-    println!(" -r<pin>          GPIO pin number for RST: Default 27");
-    println!(" -b<pin>          GPIO pin number for BLK: Default 24");
-    println!(" -x<sw>           Audio visualizer ON(1)/OFF(0): Default 0");
-    println!(" -t<offset>       Vizualizer offset millisec(0-1000): Default 500");
-    println!("                       Effective only as -x1 specified");
-}
 
-/// Get Command-line parameters.
-fn get_param() -> (u8, u8, u8, u8, u8, u8, u32) {
-    let mut spi = DEF_SPI_BUS;
-    let mut cs = DEF_CS_PIN;
-    let mut dc = DEF_GPIO_DC_PIN;
-    let mut rst = DEF_GPIO_RST_PIN;
-    let mut blk = DEF_GPIO_BLK_PIN;
-    let mut vz = 0; // Default Off
-    let mut vf = DEF_VZ_OFFSET;
-
-    for arg in env::args() {
-        if &arg[0..1] == "-" {
-            let v = &arg[2..];
-            match v.parse::<u32>() {
-                Ok(val) => match &arg[0..2] {
-                    "-s" => spi = val as u8,
-                    "-c" => cs = val as u8,
-                    "-d" => dc = val as u8,
-                    "-r" => rst = val as u8,
-                    "-b" => blk = val as u8,
-                    "-x" => vz = val as u8,
-                    "-t" => vf = val,
-                    _ => {
-                        usage();
-                        panic!()
-                    }
-                },
-                Err(_e) => {
-                    usage();
-                    panic!()
-                }
+            // SNR (update only when changed enough)
+            let snr = if sp_info.is_silent() {
+                None
+            } else {
+                Some(sp_info.estimated_snr_db())
             };
+            let changed = match (snr, self.snr_shown) {
+                (Some(new), Some(old)) => (new - old).abs() > SNR_UPDATE_DB,
+                (None, None) => false,
+                _ => true,
+            };
+            if changed || !self.snr_drawn {
+                draw_filled_rect_mut(
+                    baseimg,
+                    Rect::at(SNR_X, SNR_Y).of_size(SNR_WIDTH, SNR_HEIGHT),
+                    COLOR_BLACK,
+                );
+                let text = match snr {
+                    Some(db) if db > 0.0 => format!("SNR: {db:.0}dB"),
+                    Some(_) => String::new(),
+                    None => "SILENCE".to_string(),
+                };
+                draw_text_mut(
+                    baseimg,
+                    COLOR_WHITE,
+                    SNR_X as u32,
+                    SNR_Y as u32,
+                    self.scale_s,
+                    &self.font_n,
+                    &text,
+                );
+                self.snr_shown = snr;
+                self.snr_drawn = true;
+            }
         }
+
+        self.draw_queue_position(album_redrawn);
+        self.draw_lyrics();
+        self.draw_transition();
     }
-    (spi, cs, dc, rst, blk, vz, vf)
+}
+/// Volumio gives bitrate either as a number or as a string like "320 Kbps".
+fn deserialize_bitrate<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Option<u32>, D::Error> {
+    Ok(match serde_json::Value::deserialize(d)? {
+        serde_json::Value::Number(n) => n.as_u64().map(|n| n as u32),
+        serde_json::Value::String(s) => s
+            .split_whitespace()
+            .next()
+            .and_then(|s| u32::from_str(s).ok()),
+        _ => None,
+    })
 }
 
-/// Main routine
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let (spi_n, cs_n, dc_n, rst_n, blk_n, vz_on, vz_ofst) = get_param();
-
-    let mut state = State::new();
+/// Fetch album art and make thumbnail.
+fn fetch_thumb(client: &reqwest::blocking::Client, url: &str) -> Option<RgbaImage> {
+    let img_bytes = client.get(url).send().ok()?.bytes().ok()?;
+    let img = image::load_from_memory(&img_bytes).ok()?;
 
-    #[allow(unused_assignments)]
-    let mut sp_info;
-    let mut sp: Option<&mut SpInfo> = None;
+    let resized_img = img.resize(THUMB_WIDTH, THUMB_HEIGHT, FilterType::Triangle);
 
-    let gpio = Gpio::new().expect("Failed Gpio::new");
-    let dc_pin = gpio.get(dc_n)?.into_output();
-    let rst_pin = gpio.get(rst_n)?.into_output();
-    let blk_pin = gpio.get(blk_n)?.into_output();
-    let spi_bus = match spi_n {
-        1 => Bus::Spi1,
-        2 => Bus::Spi2,
-        _ => Bus::Spi0,
+    let x_of: i32 = if resized_img.width() >= THUMB_WIDTH {
+        0
+    } else {
+        ((THUMB_WIDTH - resized_img.width()) / 2) as i32
     };
-    let cs = match cs_n {
-        1 => SlaveSelect::Ss1,
-        2 => SlaveSelect::Ss2,
-        _ => SlaveSelect::Ss0,
+    let y_of: i32 = if resized_img.height() >= THUMB_HEIGHT {
+        0
+    } else {
+        ((THUMB_HEIGHT - resized_img.height()) / 2) as i32
     };
-    let spi = Spi::new(spi_bus, cs, SPI_MAXSPEED_HZ, spi::Mode::Mode3).expect("failed Spi::new");
-
-    let di = SPIInterfaceAutoCS::new(spi, dc_pin);
-    let mut st7789 = St7789::new(
-        di,
-        Some(rst_pin),
-        Some(blk_pin),
-        DISP_WIDTH,
-        DISP_HEIGHT,
-        ROTATION::Rot180,
+    let mut thumb = RgbaImage::new(THUMB_WIDTH, THUMB_HEIGHT);
+    draw_filled_rect_mut(
+        &mut thumb,
+        Rect::at(0, 0).of_size(THUMB_WIDTH, THUMB_HEIGHT),
+        COLOR_BLACK,
     );
-    let mut st7789img = St7789Img::new(DISP_WIDTH, DISP_HEIGHT);
-    // Display
-    st7789.init().unwrap();
+    imageops::overlay(&mut thumb, &resized_img, x_of as u32, y_of as u32);
+    Some(thumb)
+}
 
-    // for Spectrum Visualizer
-    if vz_on > 0 {
-        let fifo_fd: c_int;
-        unsafe {
-            let file_name = CString::new(MPD_FIFO_FILE).unwrap();
-            fifo_fd = libc::open(file_name.as_ptr(), libc::O_RDONLY | libc::O_NONBLOCK);
-            if fifo_fd == -1 {
-                exit(1);
-            }
+/// Draw a spectrum bar of the height in the style.
+fn draw_spectrum_bars(img: &mut RgbaImage, x: i32, width: u32, height: u32, style: SpectrumStyle) {
+    let y_center = SP_Y + (SP_HEIGHT / 2) as i32;
+    let half = cmp::max(height / 2, 1);
+    match style {
+        SpectrumStyle::BottomUp => draw_filled_rect_mut(
+            img,
+            Rect::at(x, SP_Y + (SP_HEIGHT - height) as i32).of_size(width, height),
+            COLOR_SP_BAR,
+        ),
+        SpectrumStyle::TopDown => {
+            draw_filled_rect_mut(img, Rect::at(x, SP_Y).of_size(width, height), COLOR_SP_BAR)
+        }
+        SpectrumStyle::CenterOut | SpectrumStyle::Mirror => {
+            draw_filled_rect_mut(
+                img,
+                Rect::at(x, y_center - half as i32).of_size(width, half),
+                COLOR_SP_BAR,
+            );
+            let below = if style == SpectrumStyle::Mirror {
+                weighted_sum(COLOR_SP_BAR, COLOR_BLACK, 0.5, 0.5)
+            } else {
+                COLOR_SP_BAR
+            };
+            draw_filled_rect_mut(img, Rect::at(x, y_center).of_size(width, half), below);
+        }
+        SpectrumStyle::HeightGradient => {
+            draw_gradient_bar(img, x, SP_Y + SP_HEIGHT as i32, width, height, SP_HEIGHT)
         }
-        sp_info = SpInfo::new(fifo_fd, vz_ofst);
-        sp = Some(&mut sp_info);
     }
+}
 
-    let mut is_first = true;
+/// Draw a bar up from bottom_y, colored by the height of each row in full_height.
+fn draw_gradient_bar(
+    img: &mut RgbaImage,
+    x: i32,
+    bottom_y: i32,
+    width: u32,
+    height: u32,
+    full_height: u32,
+) {
+    for row in 0..height {
+        let t = row as f32 / full_height as f32;
+        let col = weighted_sum(COLOR_SP_BAR, COLOR_SP_PEAK, 1.0 - t, t);
+        draw_filled_rect_mut(
+            img,
+            Rect::at(x, bottom_y - 1 - row as i32).of_size(width, 1),
+            col,
+        );
+    }
+}
+
+/// Draw QR code of data with quiet zone in bg at (x, y).
+fn draw_qr_code(
+    img: &mut RgbaImage,
+    data: &str,
+    x: i32,
+    y: i32,
+    module_size: u32,
+    fg: Rgba<u8>,
+    bg: Rgba<u8>,
+) {
+    let Ok(code) = QrCode::with_error_correction_level(data, EcLevel::L) else {
+        return;
+    };
+    let width = code.width() as u32;
+    let size = (width + QR_QUIET_ZONE * 2) * module_size;
+    draw_filled_rect_mut(img, Rect::at(x, y).of_size(size, size), bg);
+    let x0 = x + (QR_QUIET_ZONE * module_size) as i32;
+    let y0 = y + (QR_QUIET_ZONE * module_size) as i32;
+    for (k, color) in code.to_colors().iter().enumerate() {
+        if *color == qrcode::Color::Dark {
+            let (i, j) = (k as u32 % width, k as u32 / width);
+            draw_filled_rect_mut(
+                img,
+                Rect::at(x0 + (i * module_size) as i32, y0 + (j * module_size) as i32)
+                    .of_size(module_size, module_size),
+                fg,
+            );
+        }
+    }
+}
+
+/// IP address of the interface to the default route (no packet is sent).
+fn local_ip_address() -> Option<std::net::IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("8.8.8.8:80").ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
+
+/// Draw faint grid behind spectrum bars.
+/// Horizontal rules at SP_GRID_DB, vertical ones between the bars where
+/// cut_offs crosses SP_GRID_FQ.
+fn draw_spectrum_grid(
+    img: &mut RgbaImage,
+    sp_x: i32,
+    sp_y: i32,
+    sp_w: u32,
+    sp_h: u32,
+    cut_offs: &[f64],
+    color: Rgba<u8>,
+) {
+    for db in SP_GRID_DB {
+        let y = sp_y + (sp_h * db / SP_DYNAMIC_RANGE_DB) as i32;
+        draw_filled_rect_mut(img, Rect::at(sp_x, y).of_size(sp_w, 1), color);
+    }
+    let n = cut_offs.len() as u32;
+    for fq in SP_GRID_FQ {
+        // Bars entirely below fq
+        let i = cut_offs.iter().take_while(|&&c| c < fq).count() as u32;
+        if i > 0 && i < n {
+            // In the margin after bar i - 1
+            let x = sp_x + (sp_w * i / n) as i32 - 1;
+            draw_filled_rect_mut(img, Rect::at(x, sp_y).of_size(1, sp_h), color);
+        }
+    }
+}
+
+/// Right to left if text contains Hebrew, Arabic or similar characters.
+fn detect_text_direction(text: &str) -> TextDirection {
+    let rtl = text
+        .chars()
+        .any(|c| matches!(c as u32, 0x0590..=0x08FF | 0xFB50..=0xFDFF | 0xFE70..=0xFEFF));
+    if rtl {
+        TextDirection::Rtl
+    } else {
+        TextDirection::Ltr
+    }
+}
+
+/// Key to cache lyrics of the track.
+fn lyrics_key(info: &Info) -> String {
+    format!("{}\t{}", info.artist, info.title)
+}
+
+/// Extract k dominant colors by k-means clustering (most dominant first).
+fn extract_dominant_colors(img: &RgbaImage, k: usize) -> Vec<Rgba<u8>> {
+    let pixels: Vec<[f32; 3]> = img
+        .pixels()
+        .map(|p| [p[0] as f32, p[1] as f32, p[2] as f32])
+        .collect();
+    if pixels.is_empty() || k == 0 {
+        return Vec::new();
+    }
+
+    // Initial centers spread over the image
+    let mut centers: Vec<[f32; 3]> = (0..k)
+        .map(|i| pixels[pixels.len() * (2 * i + 1) / (2 * k)])
+        .collect();
+    let mut counts = vec![0usize; k];
+    for _ in 0..PALETTE_ITERATIONS {
+        let mut sums = vec![[0.0f32; 3]; k];
+        counts = vec![0usize; k];
+        for p in pixels.iter() {
+            let dist = |c: &[f32; 3]| -> f32 { (0..3).map(|i| (p[i] - c[i]).powi(2)).sum() };
+            let nearest = (0..k)
+                .min_by(|&a, &b| dist(&centers[a]).total_cmp(&dist(&centers[b])))
+                .unwrap();
+            for (sum, v) in sums[nearest].iter_mut().zip(p) {
+                *sum += v;
+            }
+            counts[nearest] += 1;
+        }
+        for ((center, sum), &count) in centers.iter_mut().zip(&sums).zip(&counts) {
+            if count > 0 {
+                for (c, s) in center.iter_mut().zip(sum) {
+                    *c = s / count as f32;
+                }
+            }
+        }
+    }
+
+    let mut clusters: Vec<(usize, [f32; 3])> = counts.into_iter().zip(centers).collect();
+    clusters.sort_by_key(|&(count, _)| cmp::Reverse(count));
+    clusters
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(_, c)| Rgba::<u8>([c[0] as u8, c[1] as u8, c[2] as u8, 255u8]))
+        .collect()
+}
+
+/// Theme from palette: lightest for title, most saturated for seek bar
+/// and darkest for background. Default if not enough contrast.
+fn select_theme_from_palette(palette: &[Rgba<u8>]) -> DisplayTheme {
+    let luma = |c: &Rgba<u8>| 0.299 * c[0] as f32 + 0.587 * c[1] as f32 + 0.114 * c[2] as f32;
+    // HSV saturation
+    let saturation = |c: &Rgba<u8>| {
+        let max = c[0].max(c[1]).max(c[2]) as f32;
+        let min = c[0].min(c[1]).min(c[2]) as f32;
+        if max > 0.0 {
+            (max - min) / max
+        } else {
+            0.0
+        }
+    };
+
+    let lightest = palette.iter().max_by(|a, b| luma(a).total_cmp(&luma(b)));
+    let darkest = palette.iter().min_by(|a, b| luma(a).total_cmp(&luma(b)));
+    let saturated = palette
+        .iter()
+        .max_by(|a, b| saturation(a).total_cmp(&saturation(b)));
+    match (lightest, darkest, saturated) {
+        (Some(lightest), Some(darkest), Some(saturated))
+            if luma(lightest) - luma(darkest) >= THEME_MIN_CONTRAST =>
+        {
+            DisplayTheme {
+                title_color: *lightest,
+                seek_bar_color: *saturated,
+                background_color: *darkest,
+            }
+        }
+        _ => DisplayTheme::default(),
+    }
+}
+
+/// Draw color swatches of the palette on the thumbnail.
+fn draw_album_art_palette(thumb: &mut RgbaImage, palette: &[Rgba<u8>]) {
+    for (i, col) in palette.iter().enumerate() {
+        let x = PALETTE_X + ((PALETTE_SIZE + PALETTE_MARGIN) * i as u32) as i32;
+        let rect = Rect::at(x, PALETTE_Y).of_size(PALETTE_SIZE, PALETTE_SIZE);
+        draw_filled_rect_mut(thumb, rect, *col);
+        draw_hollow_rect_mut(thumb, rect, COLOR_WHITE);
+    }
+}
+
+/// Draw up to max_len values as line graph scaled to fit in rect. Newest value is at the right end.
+fn draw_sparkline(
+    img: &mut RgbaImage,
+    rect: Rect,
+    values: &VecDeque<f32>,
+    max_len: usize,
+    color: Rgba<u8>,
+) {
+    if values.len() < 2 {
+        return;
+    }
+    let min = values.iter().cloned().fold(f32::MAX, f32::min);
+    // At least 1 degree not to magnify noise
+    let max = values
+        .iter()
+        .cloned()
+        .fold(f32::MIN, f32::max)
+        .max(min + 1.0);
+    let step = (rect.width() - 1) as f32 / (max_len.max(values.len()) - 1) as f32;
+    let x0 = rect.right() as f32 - step * (values.len() - 1) as f32;
+    let y = |v: f32| rect.bottom() as f32 - (v - min) / (max - min) * (rect.height() - 1) as f32;
+    for (i, (a, b)) in values.iter().zip(values.iter().skip(1)).enumerate() {
+        let x = x0 + step * i as f32;
+        draw_line_segment_mut(img, (x, y(*a)), (x + step, y(*b)), color);
+    }
+}
+
+/// Fill rect with horizontal gradient from left color to right color.
+fn fill_hgradient_mut(img: &mut RgbaImage, rect: Rect, left: Rgba<u8>, right: Rgba<u8>) {
+    fill_rounded_hgradient_mut(img, rect, 0, left, right);
+}
+
+/// Fill rect with rounded corners (radius 0: square).
+fn draw_rounded_rect_mut(img: &mut RgbaImage, rect: Rect, radius: u32, color: Rgba<u8>) {
+    fill_rounded_hgradient_mut(img, rect, radius, color, color);
+}
+
+/// Fill rect with rounded corners with horizontal gradient.
+fn fill_rounded_hgradient_mut(
+    img: &mut RgbaImage,
+    rect: Rect,
+    radius: u32,
+    left: Rgba<u8>,
+    right: Rgba<u8>,
+) {
+    let r = radius.min(rect.width() / 2).min(rect.height() / 2) as f32;
+    for x in rect.left()..=rect.right() {
+        let t = (x - rect.left()) as f32 / rect.width() as f32;
+        let mut col = left;
+        for c in 0..4 {
+            col[c] = (left[c] as f32 + (right[c] as f32 - left[c] as f32) * t) as u8;
+        }
+        // Distance from the corner circle center in x (pixel centers)
+        let dx = (rect.left() as f32 + r - (x as f32 + 0.5))
+            .max(x as f32 + 0.5 - (rect.right() as f32 + 1.0 - r))
+            .max(0.0);
+        let inset = if dx > 0.0 {
+            (r - (r * r - dx * dx).max(0.0).sqrt()).round() as u32
+        } else {
+            0
+        };
+        if inset * 2 < rect.height() {
+            draw_filled_rect_mut(
+                img,
+                Rect::at(x, rect.top() + inset as i32).of_size(1, rect.height() - inset * 2),
+                col,
+            );
+        }
+    }
+}
+
+/// Format seconds as "M:SS", or "H:MM:SS" for longer than 1 hour.
+fn format_time(secs: u32) -> String {
+    if secs < 3600 {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    } else {
+        format!("{}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+    }
+}
+
+/// Format seconds as "1h23m".
+fn format_hm(secs: u64) -> String {
+    format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+}
+
+/// Output Usage
+fn usage() {
+    println!("st7789volumio");
+    println!();
+    println!("Usage: st7789volumio [OPTIONS]");
+    println!();
+    println!("Options:");
+    println!(" -s<spi_bus>      SPI bus (0, 1, 2): Default 0");
+    println!(" -c<cs_pin>       Slave Select pin (0, 1, 2): Default 0");
+    println!("                       spi = 0, cs = 0...GPIO 8, 1...GPIO 7");
+    println!("                       spi = 1, cs = 0...GPIO 18, 1...GPIO 17, 2...GIPI16");
+    println!("                       spi = 2, cs = 0...GPIO 43, 1...GPIO 44, 2...GIPI45");
+    println!(" -d<pin>          GPIO pin number for DC: Default 25");
+    println!(" -r<pin>          GPIO pin number for RST: Default 27");
+    println!(" -b<pin>          GPIO pin number for BLK: Default 24");
+    println!(" -x<sw>           Audio visualizer ON(1)/OFF(0): Default 0");
+    println!(" -t<offset>       Vizualizer offset millisec(0-1000): Default 500");
+    println!("                       Effective only as -x1 specified");
+    println!(" --version        Print version and exit");
+    println!(" --list-fonts     List fonts (.ttf/.otf) in standard directories and exit");
+    println!(" --auto-detect    Take SPI/CS/DC/RST/BLK from st7789 overlay in device tree");
+    println!("                       or boot config.txt (options after it override)");
+    println!(" --udp-port <port> Receive commands on UDP port (localhost): Default off");
+    println!("                       BRIGHTNESS <0-255>, ROTATION <0|90|180|270>,");
+    println!("                       MODE <auto|clock|music|spectrum-full>, CLEAR, BARS <4-54>");
+    println!(" --health-port <port> Serve HTTP on port (localhost): Default off");
+    println!("                       GET /health, GET /screenshot (PNG), GET /metrics");
+    println!(" --api-timeout <sec> Timeout for Volumio API calls: Default 3");
+    println!(" --simulate-display <port> Run without display, stream frames as MJPEG");
+    println!("                       on HTTP port (localhost): GET /stream");
+    println!(" --reset-session-on-stop Reset session playtime when Volumio restarts");
+    println!(" --auto-recover-freeze Stop and play when Volumio seems stuck in play");
+    println!(" --adaptive-polling Poll Volumio less often (play 5s, pause/stop 10s, error 30s)");
+    println!(" --sleep-after <min> Sleep display when not playing and no command(0: off):");
+    println!("                       Default 0");
+    println!(" --adaptive-theme  Title and seek bar colors from album art");
+    println!(" --qr-code         Show QR code of Web UI URL (http://<IP address>) in clock mode");
+    println!(" --seek-height <n> Seek bar height (2-20): Default 8");
+    println!(" --seek-radius <n> Seek bar corner radius (up to half of height): Default 0");
+    println!(" --no-transitions  Disable border animation on pause/play");
+    println!(" --zoom-album-art  Zoom out from album art when it changes");
+    println!(" --stdin-control   Keys on terminal: p play/pause, n next, b prev, q quit,");
+    println!("                       +/- brightness, r rotate");
+    println!(" --show-lyrics     Show lyrics instead of album and artist after 20 sec");
+    println!(" --info-font <path> Font for information (tried in order, repeatable)");
+    println!(" --num-font <path>  Font for numbers (tried in order, repeatable)");
+    println!(" --fallback-font <path> Font for glyphs missing in info font (repeatable)");
+    println!(" --hint-text       Snap glyph advances to pixels for crisper text");
+    println!(" --sp-mode <mode>  Visualizer bars log(16)/octave(1/3-octave, 31): Default log");
+    println!(" --sp-style <style> Visualizer bars bottom-up/top-down/center-out/mirror/");
+    println!("                       height-gradient: Default bottom-up");
+    println!(" --mode <mode>     auto/clock/music/spectrum-full(spectrum fills display while");
+    println!("                       playing, needs -x1): Default auto");
+    println!(" --sp-grid         Draw grid at 100Hz/1kHz/10kHz and -20/-40/-60dB behind bars");
+    println!(" --sp-sample-rate <hz> Visualizer sample rate (same as fifo format): Default 44100");
+    println!(" --sp-hp-alpha <a> Visualizer DC cut high-pass alpha(0: off): Default 0.97");
+    println!(" --sp-bars <n>     Visualizer bars in log mode (4-54): Default 16");
+    println!(" --sp-dump-csv <path> Write bars of --sp-dump-frames frames to CSV and exit");
+    println!("                       (needs -x1)");
+    println!(" --sp-dump-frames <n> Frames to write by --sp-dump-csv: Default 1000");
+    println!(" --genre-rotation <genre>:<deg> Rotate display(0|90|180|270) while playing");
+    println!("                       the genre (case-insensitive keyword, repeatable)");
+    println!(" --x-offset <n>    Shift display window right to trim panel: Default 0");
+    println!(" --y-offset <n>    Shift display window down to trim panel: Default 0");
+    println!("                       Window must fit in 240x320 frame memory");
+    println!(" --hflip           Mirror image left to right (by software)");
+    println!(" --vflip           Mirror image top to bottom (by software)");
+    println!(" --startup-delay <ms> Wait before initializing SPI/GPIO: Default 0");
+    println!(" --startup-retries <n> Retry SPI/GPIO initialization: Default 3");
+    println!(" --spi-mode <0|3>  SPI mode (3 for most ST7789 boards): Default 3");
+    println!(" --addr-mode <8|16> Bits per CASET/RASET coordinate(8 for some panels < 256px)");
+    println!("                       Default 16");
+    println!(" --color-depth <12|16|18> Interface color depth (18: RGB666, 1.5x SPI data)");
+    println!("                       Default 16");
+    println!(" --on-display-error <mode> ignore/retry:<n>/shutdown(exit 1): Default ignore");
+    println!(" --lcm-ctrl <val>  LCMCTRL register value(e.g. 0x2C for IPS): Default not set");
+    println!(" --gctrl <val>     GCTRL register value(bit6-4: VGH, bit2-0: VGL) to reduce");
+    println!("                       ghosting(e.g. 0x35): Default not set");
+    println!(" --vrhs <val>      VRHS register value or GVDD(3.8V/4.1V/4.35V/4.45V/4.6V/4.8V)");
+    println!("                       Raise if washed out: Default 0x0B(4.1V)");
+    println!(
+        " --vdvs <val>      VDVS register value or VDV(-0.2V/-0.1V/0V/0.1V/0.2V): Default 0x20(0V)"
+    );
+    println!(" --gamma-preset <n> Gamma curves 1(Pimoroni/Waveshare 1.3in), 2(Waveshare 2in),");
+    println!("                       3(Waveshare 1.54in), 4(Waveshare 1.69in): Default not set");
+    println!(" --gamma-positive <bytes> GMCTRP1 14 hex bytes(e.g. D0,04,0D,...) to override");
+    println!(" --gamma-negative <bytes> GMCTRN1 14 hex bytes to override");
+}
+
+/// Get value for long option.
+fn get_long_value<T: FromStr>(args: &mut impl Iterator<Item = String>) -> T {
+    match args.next().map(|v| v.parse::<T>()) {
+        Some(Ok(val)) => val,
+        _ => {
+            usage();
+            panic!()
+        }
+    }
+}
+
+/// Get Command-line parameters.
+fn get_param() -> Config {
+    let mut config = Config::default();
+    let mut info_fonts: Vec<String> = Vec::new();
+    let mut num_fonts: Vec<String> = Vec::new();
+    let mut fallback_fonts: Vec<String> = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg.starts_with("--") {
+            match arg.as_str() {
+                "--version" => {
+                    println!("st7789volumio {VERSION} ({BUILD_DATE})");
+                    std::process::exit(0);
+                }
+                "--list-fonts" => {
+                    list_fonts();
+                    std::process::exit(0);
+                }
+                "--auto-detect" => match detect_pins_from_dt() {
+                    Some((spi, cs, dc, rst, blk)) => {
+                        eprintln!(
+                            "Detected st7789 overlay: spi{spi}-{cs} dc {dc} rst {rst} blk {blk}"
+                        );
+                        (config.spi, config.cs) = (spi, cs);
+                        (config.dc, config.rst, config.blk) = (dc, rst, blk);
+                    }
+                    None => eprintln!("Warning: st7789 overlay not found, using pins from options"),
+                },
+                "--udp-port" => config.udp_port = Some(get_long_value(&mut args)),
+                "--health-port" => config.health_port = Some(get_long_value(&mut args)),
+                "--simulate-display" => config.simulate_port = Some(get_long_value(&mut args)),
+                "--api-timeout" => config.api_timeout_secs = get_long_value(&mut args),
+                "--reset-session-on-stop" => config.reset_session_on_stop = true,
+                "--auto-recover-freeze" => config.auto_recover_freeze = true,
+                "--show-lyrics" => config.show_lyrics = true,
+                "--no-transitions" => config.enable_transitions = false,
+                "--zoom-album-art" => config.zoom_album_art = true,
+                "--hflip" => config.horizontal_flip = true,
+                "--vflip" => config.vertical_flip = true,
+                "--stdin-control" => config.stdin_control = true,
+                "--adaptive-theme" => config.adaptive_theme = true,
+                "--qr-code" => config.show_qr_code = true,
+                "--seek-height" => {
+                    config.seek_bar_height = get_long_value(&mut args);
+                    if !(SEEK_MIN_HEIGHT..=SEEK_MAX_HEIGHT).contains(&config.seek_bar_height) {
+                        usage();
+                        panic!()
+                    }
+                }
+                "--seek-radius" => config.seek_bar_corner_radius = get_long_value(&mut args),
+                "--adaptive-polling" => config.adaptive_polling = true,
+                "--sleep-after" => config.sleep_after_min = get_long_value(&mut args),
+                "--info-font" => info_fonts.push(get_long_value(&mut args)),
+                "--num-font" => num_fonts.push(get_long_value(&mut args)),
+                "--fallback-font" => fallback_fonts.push(get_long_value(&mut args)),
+                "--hint-text" => config.hint_text = true,
+                "--sp-sample-rate" => config.sp_sample_rate = get_long_value(&mut args),
+                "--sp-mode" => config.sp_mode = get_long_value(&mut args),
+                "--sp-style" => config.spectrum_style = get_long_value(&mut args),
+                "--sp-grid" => config.spectrum_grid = true,
+                "--mode" => config.display_mode = get_long_value(&mut args),
+                "--sp-hp-alpha" => config.sp_hp_alpha = get_long_value(&mut args),
+                "--sp-dump-csv" => config.sp_dump_csv = Some(get_long_value(&mut args)),
+                "--sp-dump-frames" => config.sp_dump_frames = get_long_value(&mut args),
+                "--sp-bars" => {
+                    config.sp_bars = get_long_value(&mut args);
+                    if !(MIN_NUM_BARS..=MAX_NUM_BARS).contains(&config.sp_bars) {
+                        usage();
+                        panic!()
+                    }
+                }
+                "--genre-rotation" => {
+                    let arg: String = get_long_value(&mut args);
+                    match arg
+                        .rsplit_once(':')
+                        .and_then(|(genre, deg)| Some((genre, parse_rotation(deg)?)))
+                    {
+                        Some((genre, rotation)) => {
+                            config
+                                .genre_rotations
+                                .insert(genre.to_lowercase(), rotation);
+                        }
+                        None => {
+                            usage();
+                            panic!()
+                        }
+                    }
+                }
+                "--x-offset" => config.x_offset = get_long_value(&mut args),
+                "--y-offset" => config.y_offset = get_long_value(&mut args),
+                "--startup-delay" => config.startup_delay_ms = get_long_value(&mut args),
+                "--startup-retries" => config.startup_retries = get_long_value(&mut args),
+                "--spi-mode" => {
+                    config.spi_mode = match get_long_value::<u8>(&mut args) {
+                        0 => SpiMode::Mode0,
+                        3 => SpiMode::Mode3,
+                        _ => {
+                            usage();
+                            panic!()
+                        }
+                    }
+                }
+                "--addr-mode" => {
+                    config.addr_mode = match get_long_value::<u8>(&mut args) {
+                        8 => AddressingMode::Bit8,
+                        16 => AddressingMode::Bit16,
+                        _ => {
+                            usage();
+                            panic!()
+                        }
+                    }
+                }
+                "--color-depth" => {
+                    config.color_depth = match get_long_value::<u8>(&mut args) {
+                        12 => ColorDepth::Bits12,
+                        16 => ColorDepth::Bits16,
+                        18 => ColorDepth::Bits18,
+                        _ => {
+                            usage();
+                            panic!()
+                        }
+                    }
+                }
+                "--on-display-error" => config.error_mode = get_long_value(&mut args),
+                "--lcm-ctrl" => {
+                    let val: String = get_long_value(&mut args);
+                    match parse_register(&val) {
+                        Some(v) => config.lcm_ctrl = Some(v),
+                        None => {
+                            usage();
+                            panic!()
+                        }
+                    }
+                }
+                "--gctrl" => {
+                    let val: String = get_long_value(&mut args);
+                    match parse_register(&val) {
+                        Some(v) if v & 0x88 == 0 => config.gctrl = Some(v),
+                        _ => {
+                            usage();
+                            panic!()
+                        }
+                    }
+                }
+                "--vrhs" | "--vdvs" => {
+                    let val: String = get_long_value(&mut args);
+                    let table: &[(f32, u8)] = if arg == "--vrhs" {
+                        &VRHS_TABLE
+                    } else {
+                        &VDVS_TABLE
+                    };
+                    let parsed = match val.strip_suffix(['V', 'v']) {
+                        Some(volts) => volts
+                            .parse::<f32>()
+                            .ok()
+                            .and_then(|v| register_for_voltage(table, v)),
+                        None => parse_register(&val),
+                    };
+                    let Some(value) = parsed else {
+                        usage();
+                        panic!()
+                    };
+                    if arg == "--vrhs" {
+                        config.vrhs = value;
+                    } else {
+                        config.vdvs = value;
+                    }
+                }
+                "--gamma-preset" => {
+                    config.gamma_preset = match get_long_value::<u8>(&mut args) {
+                        1 => Some(GAMMA_PRESET_1),
+                        2 => Some(GAMMA_PRESET_2),
+                        3 => Some(GAMMA_PRESET_3),
+                        4 => Some(GAMMA_PRESET_4),
+                        _ => {
+                            usage();
+                            panic!()
+                        }
+                    }
+                }
+                "--gamma-positive" | "--gamma-negative" => {
+                    let val: String = get_long_value(&mut args);
+                    let Some(bytes) = parse_gamma_bytes(&val) else {
+                        usage();
+                        panic!()
+                    };
+                    if arg == "--gamma-positive" {
+                        config.gamma_positive = Some(bytes);
+                    } else {
+                        config.gamma_negative = Some(bytes);
+                    }
+                }
+                _ => {
+                    usage();
+                    panic!()
+                }
+            }
+        } else if &arg[0..1] == "-" {
+            let v = &arg[2..];
+            match v.parse::<u32>() {
+                Ok(val) => match &arg[0..2] {
+                    "-s" => config.spi = val as u8,
+                    "-c" => config.cs = val as u8,
+                    "-d" => config.dc = val as u8,
+                    "-r" => config.rst = val as u8,
+                    "-b" => config.blk = val as u8,
+                    "-x" => config.vz = val as u8,
+                    "-t" => config.vz_offset = val,
+                    _ => {
+                        usage();
+                        panic!()
+                    }
+                },
+                Err(_e) => {
+                    usage();
+                    panic!()
+                }
+            };
+        }
+    }
+    // Specified fonts take priority over defaults.
+    fallback_fonts.append(&mut config.fallback_fonts);
+    config.fallback_fonts = fallback_fonts;
+    info_fonts.append(&mut config.info_fonts);
+    config.info_fonts = info_fonts;
+    num_fonts.append(&mut config.num_fonts);
+    config.num_fonts = num_fonts;
+
+    config
+}
+
+/// Rotation for the genre (default if no keyword matches).
+fn genre_rotation(genre_rotations: &HashMap<String, ROTATION>, genre: &str) -> ROTATION {
+    let genre = genre.to_lowercase();
+    genre_rotations
+        .iter()
+        .find(|(keyword, _)| genre.contains(keyword.as_str()))
+        .map(|(_, rotation)| *rotation)
+        .unwrap_or(DEF_ROTATION)
+}
+
+/// Reset display or shut down by error mode (errors: consecutive count).
+fn on_display_error<DI: WriteOnlyDataCommand>(
+    st7789: &mut St7789<DI>,
+    mode: ErrorMode,
+    errors: u32,
+) {
+    match mode {
+        ErrorMode::Ignore => {}
+        ErrorMode::Retry(n) if errors <= n => {
+            eprintln!("Resetting display ({errors}/{n})");
+            if let Err(_e) = st7789.init() {
+                eprintln!("Failed st7789 init");
+            }
+        }
+        ErrorMode::Retry(_) | ErrorMode::Shutdown => {
+            eprintln!("Shutting down display");
+            let _ = st7789.shutdown();
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Parse register value in hex (0x2C) or decimal.
+fn parse_register(val: &str) -> Option<u8> {
+    match val.strip_prefix("0x").or(val.strip_prefix("0X")) {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => val.parse::<u8>().ok(),
+    }
+}
+
+/// Parse 14 hex bytes separated by comma, colon or space (e.g. D0,04,0D,...).
+fn parse_gamma_bytes(val: &str) -> Option<[u8; 14]> {
+    let bytes = val
+        .split(|c: char| c == ',' || c == ':' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            let hex = s.strip_prefix("0x").or(s.strip_prefix("0X")).unwrap_or(s);
+            u8::from_str_radix(hex, 16).ok()
+        })
+        .collect::<Option<Vec<u8>>>()?;
+    bytes.try_into().ok()
+}
+
+/// Gamma from preset and overrides (preset 1 for the half not overridden).
+fn gamma_from_config(config: &Config) -> Option<Gamma> {
+    if config.gamma_preset.is_none()
+        && config.gamma_positive.is_none()
+        && config.gamma_negative.is_none()
+    {
+        return None;
+    }
+    let mut gamma = config.gamma_preset.unwrap_or(GAMMA_PRESET_1);
+    if let Some(positive) = config.gamma_positive {
+        gamma.positive = positive;
+    }
+    if let Some(negative) = config.gamma_negative {
+        gamma.negative = negative;
+    }
+    Some(gamma)
+}
+
+/// Retry initialization failed just after boot.
+fn retry_startup<T, E: std::fmt::Display>(
+    name: &str,
+    config: &Config,
+    mut f: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    let mut retries = 0;
+    loop {
+        match f() {
+            Err(e) if retries < config.startup_retries => {
+                retries += 1;
+                eprintln!(
+                    "Warning: failed {name} ({e}), retry {retries}/{}",
+                    config.startup_retries
+                );
+                thread::sleep(Duration::from_millis(config.startup_delay_ms));
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Load snapshot saved at last shutdown.
+fn load_snapshot(path: &str) -> Option<StateSnapshot> {
+    let json = fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&json) {
+        Ok(snap) => Some(snap),
+        Err(e) => {
+            eprintln!("Warning: ignore broken snapshot {path}: {e}");
+            None
+        }
+    }
+}
+
+/// Save snapshot to restore at next start.
+fn save_snapshot(path: &str, snap: &StateSnapshot) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string(snap)?)?;
+    Ok(())
+}
+
+static TERMINATED: AtomicBool = AtomicBool::new(false);
+// Exported on GET /metrics
+static DISPLAY_ERRORS: AtomicU32 = AtomicU32::new(0);
+
+extern "C" fn on_sigterm(_: c_int) {
+    TERMINATED.store(true, Ordering::SeqCst);
+}
+
+/// Serve health check, metrics and screenshot on HTTP.
+/// Screenshot is requested to main loop, which replies the PNG of the last frame.
+fn start_health_server(port: u16, screenshot_tx: Sender<Sender<Vec<u8>>>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request_line = String::new();
+            if BufReader::new(&stream)
+                .read_line(&mut request_line)
+                .is_err()
+            {
+                continue;
+            }
+            let mut words = request_line.split_whitespace();
+            let (status, content_type, body) = match (words.next(), words.next()) {
+                (Some("GET"), Some("/health")) => ("200 OK", "text/plain", b"OK".to_vec()),
+                (Some("GET"), Some("/metrics")) => {
+                    let body = format!(
+                        "st7789_display_errors_total {}\n",
+                        DISPLAY_ERRORS.load(Ordering::Relaxed)
+                    );
+                    ("200 OK", "text/plain; version=0.0.4", body.into_bytes())
+                }
+                (Some("GET"), Some("/screenshot")) => {
+                    let (tx, rx) = mpsc::channel();
+                    if screenshot_tx.send(tx).is_err() {
+                        // main loop has gone
+                        return;
+                    }
+                    match rx.recv_timeout(Duration::from_secs(SCREENSHOT_TIMEOUT_SEC)) {
+                        Ok(png) => ("200 OK", "image/png", png),
+                        Err(_) => ("503 Service Unavailable", "text/plain", Vec::new()),
+                    }
+                }
+                _ => ("404 Not Found", "text/plain", Vec::new()),
+            };
+            let header = format!(
+                "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream
+                .write_all(header.as_bytes())
+                .and_then(|_| stream.write_all(&body));
+        }
+    });
+    Ok(())
+}
+
+/// Senders of shared JPEG frames, one per connected client.
+type MjpegClients = Arc<Mutex<Vec<Sender<Arc<Vec<u8>>>>>>;
+
+/// Stream frames as MJPEG on HTTP (GET /stream).
+/// Returns sender to pass JPEG frames to the clients.
+fn start_mjpeg_server(port: u16) -> std::io::Result<Sender<Vec<u8>>> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let clients: MjpegClients = Arc::default();
+    let (frame_tx, frame_rx) = mpsc::channel::<Vec<u8>>();
+
+    let dispatch_clients = Arc::clone(&clients);
+    thread::spawn(move || {
+        for frame in frame_rx {
+            let frame = Arc::new(frame);
+            // Disconnected clients are dropped here
+            dispatch_clients
+                .lock()
+                .unwrap()
+                .retain(|tx| tx.send(Arc::clone(&frame)).is_ok());
+        }
+    });
+
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request_line = String::new();
+            if BufReader::new(&stream)
+                .read_line(&mut request_line)
+                .is_err()
+            {
+                continue;
+            }
+            let mut words = request_line.split_whitespace();
+            if let (Some("GET"), Some("/stream")) = (words.next(), words.next()) {
+                let (tx, rx) = mpsc::channel::<Arc<Vec<u8>>>();
+                clients.lock().unwrap().push(tx);
+                thread::spawn(move || {
+                    let header = "HTTP/1.1 200 OK\r\n\
+                         Content-Type: multipart/x-mixed-replace; boundary=frame\r\n\
+                         Cache-Control: no-cache\r\nConnection: close\r\n\r\n";
+                    if stream.write_all(header.as_bytes()).is_err() {
+                        return;
+                    }
+                    for jpeg in rx {
+                        let part = format!(
+                            "--frame\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+                            jpeg.len()
+                        );
+                        if stream
+                            .write_all(part.as_bytes())
+                            .and_then(|_| stream.write_all(&jpeg))
+                            .and_then(|_| stream.write_all(b"\r\n"))
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                });
+            } else {
+                let _ = stream.write_all(
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                );
+            }
+        }
+    });
+    Ok(frame_tx)
+}
+
+/// Collect .ttf/.otf files under dir recursively.
+fn find_font_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                find_font_files(&path, files);
+            } else if path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| e.eq_ignore_ascii_case("ttf") || e.eq_ignore_ascii_case("otf"))
+            {
+                files.push(path);
+            }
+        }
+    }
+}
+
+/// Print fonts in FONT_DIRS and whether they can be loaded.
+fn list_fonts() {
+    for dir in FONT_DIRS {
+        println!("{dir}:");
+        let mut files = Vec::new();
+        find_font_files(Path::new(dir), &mut files);
+        files.sort();
+        if files.is_empty() {
+            println!("    (none)");
+        }
+        for file in files {
+            let ok = fs::read(&file).ok().and_then(Font::try_from_vec).is_some();
+            println!("    {} {}", if ok { "OK" } else { "NG" }, file.display());
+        }
+    }
+}
+
+/// Pins (spi_bus, cs, dc, rst, blk) of the st7789 overlay.
+/// Device tree is tried first, then dtoverlay lines in the boot config.
+fn detect_pins_from_dt() -> Option<(u8, u8, u8, u8, u8)> {
+    find_dt_node(Path::new(DT_ROOT), 0)
+        .and_then(|node| pins_from_dt_node(&node))
+        .or_else(|| {
+            BOOT_CONFIG_FILES
+                .iter()
+                .find_map(|f| pins_from_boot_config(f))
+        })
+}
+
+/// Directory of the device tree node compatible with st7789.
+fn find_dt_node(dir: &Path, depth: u32) -> Option<PathBuf> {
+    if let Ok(compatible) = fs::read(dir.join("compatible")) {
+        if String::from_utf8_lossy(&compatible).contains("st7789") {
+            return Some(dir.to_path_buf());
+        }
+    }
+    if depth >= DT_MAX_DEPTH {
+        return None;
+    }
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .find_map(|e| find_dt_node(&e.path(), depth + 1))
+}
+
+/// index-th big-endian cell of a device tree property.
+fn dt_cell(path: &Path, index: usize) -> Option<u32> {
+    let bytes = fs::read(path).ok()?;
+    let cell = bytes.get(index * 4..index * 4 + 4)?;
+    Some(u32::from_be_bytes(cell.try_into().ok()?))
+}
+
+fn pins_from_dt_node(node: &Path) -> Option<(u8, u8, u8, u8, u8)> {
+    let cs = dt_cell(&node.join("reg"), 0)? as u8;
+    // <&gpio pin flags>
+    let dc = dt_cell(&node.join("dc-gpios"), 1)? as u8;
+    let rst = dt_cell(&node.join("reset-gpios"), 1)? as u8;
+    let blk = ["led-gpios", "backlight-gpios"]
+        .iter()
+        .find_map(|p| dt_cell(&node.join(p), 1))
+        .map_or(DEF_GPIO_BLK_PIN, |pin| pin as u8);
+    // Bus number from aliases like spi0 = "/soc/spi@7e204000"
+    let parent = node.parent()?.file_name()?.to_str()?;
+    let aliases = Path::new(DT_ROOT).join("aliases");
+    let spi = (0..=6u8).find(|n| {
+        fs::read_to_string(aliases.join(format!("spi{n}")))
+            .is_ok_and(|path| path.trim_end_matches('\0').ends_with(parent))
+    })?;
+    Some((spi, cs, dc, rst, blk))
+}
+
+/// Parse a line like "dtoverlay=fbtft,spi0-0,st7789v,dc_pin=25,reset_pin=27,led_pin=24".
+/// Pins not given are default.
+fn pins_from_boot_config(path: &str) -> Option<(u8, u8, u8, u8, u8)> {
+    let text = fs::read_to_string(path).ok()?;
+    let params = text
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("dtoverlay="))
+        .find(|line| line.contains("st7789"))?;
+    let mut pins = (
+        DEF_SPI_BUS,
+        DEF_CS_PIN,
+        DEF_GPIO_DC_PIN,
+        DEF_GPIO_RST_PIN,
+        DEF_GPIO_BLK_PIN,
+    );
+    for param in params.split(',') {
+        if let Some((bus, cs)) = param.strip_prefix("spi").and_then(|p| p.split_once('-')) {
+            if let (Ok(bus), Ok(cs)) = (bus.parse(), cs.parse()) {
+                (pins.0, pins.1) = (bus, cs);
+            }
+            continue;
+        }
+        let Some((key, val)) = param.split_once('=') else {
+            continue;
+        };
+        let Ok(pin) = val.parse::<u8>() else {
+            continue;
+        };
+        match key {
+            "dc_pin" | "dc-gpio" => pins.2 = pin,
+            "reset_pin" | "reset-gpio" => pins.3 = pin,
+            "led_pin" | "backlight-gpio" => pins.4 = pin,
+            _ => {}
+        }
+    }
+    Some(pins)
+}
+
+/// Receive remote commands on UDP and pass them to main loop.
+fn start_udp_listener(port: u16, tx: Sender<DisplayCommand>) -> std::io::Result<()> {
+    let socket = UdpSocket::bind(("127.0.0.1", port))?;
+
+    thread::spawn(move || {
+        let mut buf = [0u8; UDP_BUFF_LEN];
+        loop {
+            if let Ok((len, _)) = socket.recv_from(&mut buf) {
+                let msg = String::from_utf8_lossy(&buf[..len]);
+                for line in msg.lines() {
+                    match DisplayCommand::parse(line) {
+                        Some(cmd) => {
+                            if tx.send(cmd).is_err() {
+                                // main loop has gone
+                                return;
+                            }
+                        }
+                        None => eprintln!("Unknown command: {line}"),
+                    }
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Main routine
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = get_param();
+
+    let mut state = State::try_new(
+        &config.info_fonts,
+        &config.num_fonts,
+        &config.fallback_fonts,
+        config.api_timeout_secs,
+    )?;
+    state.reset_session_on_stop = config.reset_session_on_stop;
+    state.auto_recover_freeze = config.auto_recover_freeze;
+    state.show_lyrics = config.show_lyrics;
+    state.adaptive_polling = config.adaptive_polling;
+    state.enable_transitions = config.enable_transitions;
+    state.zoom_album_art = config.zoom_album_art;
+    state.adaptive_theme = config.adaptive_theme;
+    state.hint_text = config.hint_text;
+    state.spectrum_style = config.spectrum_style;
+    state.spectrum_grid = config.spectrum_grid;
+    state.show_qr_code = config.show_qr_code;
+    state.layout = DisplayLayout::from_config(&config);
+
+    #[allow(unused_assignments)]
+    let mut sp_info;
+    let mut sp: Option<&mut SpInfo> = None;
+
+    let (di, rst_pin, blk_pin): (Box<dyn DataCommand>, _, _) = if config.simulate_port.is_some() {
+        // No display hardware, frames go to MJPEG stream
+        (Box::new(MockInterface::new()), None, None)
+    } else {
+        // Wait for SPI/GPIO to be ready after boot
+        thread::sleep(Duration::from_millis(config.startup_delay_ms));
+        let gpio = retry_startup("Gpio::new", &config, Gpio::new).expect("Failed Gpio::new");
+        let dc_pin = gpio.get(config.dc)?.into_output();
+        let rst_pin = gpio.get(config.rst)?.into_output();
+        let blk_pin = gpio.get(config.blk)?.into_output();
+        let spi_bus = match config.spi {
+            1 => Bus::Spi1,
+            2 => Bus::Spi2,
+            _ => Bus::Spi0,
+        };
+        let cs = match config.cs {
+            1 => SlaveSelect::Ss1,
+            2 => SlaveSelect::Ss2,
+            _ => SlaveSelect::Ss0,
+        };
+        let spi = retry_startup("Spi::new", &config, || {
+            Spi::new(spi_bus, cs, SPI_MAXSPEED_HZ, config.spi_mode.into())
+        })
+        .expect("failed Spi::new");
+
+        (
+            Box::new(RppalSPIInterfaceAutoCS::new(spi, dc_pin)),
+            Some(rst_pin),
+            Some(blk_pin),
+        )
+    };
+    let mut builder = St7789Builder::new()
+        .with_dimensions(DISP_WIDTH, DISP_HEIGHT)
+        .with_rotation(DEF_ROTATION);
+    if let Some(pin) = rst_pin {
+        builder = builder.with_reset_pin(pin);
+    }
+    if let Some(pin) = blk_pin {
+        builder = builder.with_backlight_pin(pin);
+    }
+    let mut st7789 = builder.build(di)?;
+    if st7789
+        .set_display_offset(config.x_offset, config.y_offset)
+        .is_err()
+    {
+        eprintln!(
+            "Warning: --x-offset {} --y-offset {} out of the frame memory, ignored",
+            config.x_offset, config.y_offset
+        );
+    }
+    if st7789.set_addressing_mode(config.addr_mode).is_err() {
+        eprintln!("Warning: 8-bit addressing needs display smaller than 256px");
+    }
+    st7789.set_on_display_error(Some(Box::new(|_: &st7789volumio::Error| {
+        DISPLAY_ERRORS.fetch_add(1, Ordering::Relaxed);
+    })));
+    let mut st7789img = St7789Img::new(DISP_WIDTH, DISP_HEIGHT, config.color_depth);
+    let mut zoom_img = St7789Img::new(DISP_WIDTH, DISP_HEIGHT, config.color_depth);
+    // Display
+    st7789.init()?;
+    if let Err(_e) = st7789.set_color_mode(config.color_depth) {
+        eprintln!("Failed st7789 set_color_mode");
+    }
+    if let Some(value) = config.lcm_ctrl {
+        if let Err(_e) = st7789.set_lcm_control(value) {
+            eprintln!("Failed st7789 set_lcm_control");
+        }
+    }
+    if let Some(value) = config.gctrl {
+        if let Err(_e) = st7789.set_gate_control(value >> 4, value & 0x07) {
+            eprintln!("Failed st7789 set_gate_control");
+        }
+    }
+    if st7789.set_vrhs(config.vrhs).is_err() || st7789.set_vdvs(config.vdvs).is_err() {
+        eprintln!("Failed st7789 set_vrhs/set_vdvs");
+    }
+    if let Some(gamma) = gamma_from_config(&config) {
+        if let Err(_e) = st7789.set_gamma(gamma) {
+            eprintln!("Failed st7789 set_gamma");
+        }
+    }
+    if config.simulate_port.is_none() {
+        match st7789.verify_init_sequence() {
+            Ok(mismatches) => {
+                for (cmd, read, exp) in mismatches {
+                    eprintln!(
+                        "Warning: register {cmd:#04x} reads {read:#04x}, expected {exp:#04x}"
+                    );
+                }
+            }
+            Err(_e) => eprintln!("Failed st7789 verify_init_sequence"),
+        }
+    }
+
+    // Splash
+    state.draw_splash();
+    st7789img.set_image(&mut state.baseimg);
+    if let Err(_e) = st7789.display_img(&st7789img) {
+        eprintln!("Failed st7789 display_img");
+    }
+    thread::sleep(Duration::from_millis(SPLASH_MSEC));
+    state.clear();
+    if let Some(snap) = load_snapshot(SNAPSHOT_FILE) {
+        state.apply_snapshot(snap);
+    }
+    unsafe {
+        libc::signal(libc::SIGTERM, on_sigterm as *const () as libc::sighandler_t);
+    }
+
+    // for Spectrum Visualizer
+    if config.vz > 0 {
+        let fifo_fd: c_int;
+        unsafe {
+            let file_name = CString::new(MPD_FIFO_FILE).unwrap();
+            fifo_fd = libc::open(file_name.as_ptr(), libc::O_RDONLY | libc::O_NONBLOCK);
+            if fifo_fd == -1 {
+                exit(1);
+            }
+        }
+        sp_info = SpInfo::new(
+            fifo_fd,
+            config.vz_offset,
+            config.sp_sample_rate,
+            config.sp_mode,
+        )
+        .with_high_pass_filter(config.sp_hp_alpha)
+        .with_num_bars(config.sp_bars);
+        if let Some(path) = &config.sp_dump_csv {
+            sp_info.dump_fft_csv(Path::new(path), config.sp_dump_frames)?;
+            println!("Wrote {} frames to {path}", config.sp_dump_frames);
+            return Ok(());
+        }
+        sp = Some(&mut sp_info);
+    }
+    if config.sp_dump_csv.is_some() && sp.is_none() {
+        eprintln!("Warning: --sp-dump-csv needs -x1, ignored");
+    }
+
+    // for remote commands
+    let (tx, rx) = mpsc::channel::<DisplayCommand>();
+    state.genre_rotations = config.genre_rotations;
+    state.rotation_tx = Some(tx.clone());
+    if let Some(port) = config.udp_port {
+        start_udp_listener(port, tx.clone())?;
+    }
+    let _stdin_controller = if config.stdin_control {
+        Some(StdinController::start(tx)?)
+    } else {
+        None
+    };
+    let (screenshot_tx, screenshot_rx) = mpsc::channel::<Sender<Vec<u8>>>();
+    if let Some(port) = config.health_port {
+        start_health_server(port, screenshot_tx)?;
+    }
+    let mjpeg_tx = match config.simulate_port {
+        Some(port) => Some(start_mjpeg_server(port)?),
+        None => None,
+    };
+    let mut mjpeg_t = Instant::now();
+    let mut display_mode = config.display_mode;
+    let mut pre_draw_mode = DrawMode::Music;
+    let mut brightness = u8::MAX;
+    let error_mode = config.error_mode;
+    let (hflip, vflip) = (config.horizontal_flip, config.vertical_flip);
+    let mut display_errors = 0u32;
+    let sleep_after = Duration::from_secs(config.sleep_after_min as u64 * 60);
+    let mut idle_t = Instant::now();
+    let mut sleeping = false;
+    if error_mode != ErrorMode::Ignore {
+        eprintln!("Display error mode: {error_mode:?}");
+    }
+
+    let mut is_first = true;
     let mut now_t = Instant::now();
     let mut pre_t = now_t;
+    let mut frame_t = now_t;
+
+    while !TERMINATED.load(Ordering::SeqCst) {
+        while let Ok(cmd) = rx.try_recv() {
+            idle_t = Instant::now();
+            match cmd {
+                DisplayCommand::SetBrightness(val) => {
+                    // Backlight is only on/off, so dim by software
+                    let _ = st7789.set_backlight(val > 0);
+                    brightness = val;
+                }
+                DisplayCommand::AdjustBrightness(delta) => {
+                    let val = (brightness as i16 + delta).clamp(0, u8::MAX as i16) as u8;
+                    let _ = st7789.set_backlight(val > 0);
+                    brightness = val;
+                }
+                DisplayCommand::SetRotation(rotation) => {
+                    if let Err(_e) = st7789.set_rotation(rotation) {
+                        eprintln!("Failed st7789 set_rotation");
+                    }
+                }
+                DisplayCommand::Rotate => {
+                    let rotation = match st7789.get_rotation() {
+                        ROTATION::Rot0 => ROTATION::Rot90,
+                        ROTATION::Rot90 => ROTATION::Rot180,
+                        ROTATION::Rot180 => ROTATION::Rot270,
+                        ROTATION::Rot270 => ROTATION::Rot0,
+                    };
+                    if let Err(_e) = st7789.set_rotation(rotation) {
+                        eprintln!("Failed st7789 set_rotation");
+                    }
+                }
+                DisplayCommand::Player(cmd) => state.send_player_command(cmd),
+                DisplayCommand::Quit => TERMINATED.store(true, Ordering::SeqCst),
+                DisplayCommand::SetMode(mode) => {
+                    display_mode = mode;
+                    state.clear();
+                    is_first = true;
+                }
+                DisplayCommand::Clear => {
+                    state.clear();
+                    is_first = true;
+                }
+                DisplayCommand::SetNumBars(num_bars) => {
+                    // bar_vals is resized on next draw_music_info
+                    if let Some(ref mut sp_info) = sp {
+                        if !sp_info.set_num_bars(num_bars) {
+                            eprintln!(
+                                "Warning: number of bars must be {MIN_NUM_BARS}-{MAX_NUM_BARS}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
 
-    loop {
         now_t = Instant::now();
         let dur = now_t.duration_since(pre_t);
 
-        if dur.as_secs() > INFO_INTERVAL_SEC || is_first {
+        // Session playtime
+        if state.pre_info.is_playing() {
+            state.count_session(now_t.duration_since(frame_t).as_millis() as u64);
+        }
+        frame_t = now_t;
+
+        if dur.as_secs() > state.info_interval_sec || is_first {
             pre_t = now_t;
             is_first = false;
             let _ = state.update_state();
         }
-        let interval = if state.pre_info.status.eq("play") {
-            state.draw_music_info(&mut sp);
-            DISP_INTERVAL_MSEC
-        } else {
-            state.draw_clock();
-            CLOCK_INTERVAL_MSEC
+        let new_mode = match display_mode {
+            DisplayMode::Auto | DisplayMode::SpectrumFull => AppMode::from_info(&state.pre_info),
+            DisplayMode::Clock => AppMode::Clock,
+            DisplayMode::Music if state.pre_info.is_paused() => {
+                AppMode::Paused(state.pre_info.clone())
+            }
+            DisplayMode::Music => AppMode::Playing(state.pre_info.clone()),
+        };
+        if mem::discriminant(&new_mode) != mem::discriminant(&state.mode) {
+            state.transition_to(new_mode);
+        }
+        let draw_mode = match state.mode.draw_mode() {
+            DrawMode::Music if display_mode == DisplayMode::SpectrumFull && sp.is_some() => {
+                DrawMode::SpectrumFull
+            }
+            mode => mode,
+        };
+        // Only clock area is active in clock mode
+        if draw_mode != pre_draw_mode {
+            let res = if draw_mode == DrawMode::Clock {
+                st7789.set_partial_window(CLOCK_PARTIAL_START, CLOCK_PARTIAL_END)
+            } else if pre_draw_mode == DrawMode::Clock {
+                st7789.exit_partial_window()
+            } else {
+                Ok(())
+            };
+            if let Err(_e) = res {
+                eprintln!("Failed st7789 partial window");
+            }
+            if draw_mode == DrawMode::SpectrumFull || pre_draw_mode == DrawMode::SpectrumFull {
+                if let Some(ref mut sp_info) = sp {
+                    sp_info.set_num_bars(if draw_mode == DrawMode::SpectrumFull {
+                        FULL_SP_BARS
+                    } else {
+                        config.sp_bars
+                    });
+                }
+            }
+            if pre_draw_mode == DrawMode::SpectrumFull {
+                // Music and stop screens are drawn only where changed
+                state.clear();
+                is_first = true;
+            }
+            pre_draw_mode = draw_mode;
+        }
+        // Sleep display while idle
+        if state.pre_info.is_playing() {
+            idle_t = now_t;
+        }
+        let idle = config.sleep_after_min > 0 && now_t.duration_since(idle_t) >= sleep_after;
+        if idle != sleeping {
+            let res = if idle {
+                st7789.sleep().and_then(|_| st7789.set_backlight(false))
+            } else {
+                st7789
+                    .wake()
+                    .and_then(|_| st7789.set_backlight(brightness > 0))
+            };
+            if let Err(_e) = res {
+                eprintln!("Failed st7789 sleep/wake");
+            }
+            sleeping = idle;
+        }
+        let (interval, redraw) = match draw_mode {
+            DrawMode::Music => {
+                state.draw_music_info(&mut sp);
+                (DISP_INTERVAL_MSEC, true)
+            }
+            DrawMode::Stop => {
+                state.draw_stop();
+                (STOP_INTERVAL_MSEC, true)
+            }
+            DrawMode::Clock => (CLOCK_INTERVAL_MSEC, state.draw_clock()),
+            DrawMode::SpectrumFull => {
+                if let Some(ref mut sp_info) = sp {
+                    state.draw_fullscreen_spectrum(sp_info);
+                }
+                (DISP_INTERVAL_MSEC, true)
+            }
         };
 
-        let baseimg = &mut state.baseimg;
-        st7789img.set_image(baseimg);
-        if let Err(_e) = st7789.display_img(&st7789img) {
-            // Might be panic and exit is much better...
-            eprintln!("Failed st7789 display_img");
+        if redraw && !sleeping {
+            st7789img.set_image_with_alpha_bg(&state.baseimg, state.current_theme.background_color);
+            if draw_mode == DrawMode::Stop {
+                grey_image_in_place(&mut st7789img);
+                st7789img.overlay_image(&state.stop_overlay);
+            }
+            st7789img.apply_brightness_inplace(brightness as f32 / u8::MAX as f32);
+            let frame = match state.next_zoom_rect() {
+                Some((x, y, w, h))
+                    if draw_mode == DrawMode::Music
+                        && st7789img
+                            .scale_subregion_into(x, y, w, h, &mut zoom_img)
+                            .is_ok() =>
+                {
+                    &mut zoom_img
+                }
+                _ => &mut st7789img,
+            };
+            if hflip {
+                frame.apply_horizontal_flip_inplace();
+            }
+            if vflip {
+                frame.apply_vertical_flip_inplace();
+            }
+            if let Err(_e) = st7789.display_img(frame) {
+                eprintln!("Failed st7789 display_img");
+                display_errors += 1;
+                on_display_error(&mut st7789, error_mode, display_errors);
+            } else {
+                display_errors = 0;
+            }
+        }
+
+        while let Ok(reply) = screenshot_rx.try_recv() {
+            match st7789img.to_png_bytes() {
+                Ok(png) => {
+                    let _ = reply.send(png);
+                }
+                Err(e) => eprintln!("Failed to encode screenshot: {e}"),
+            }
+        }
+
+        if let Some(ref frame_tx) = mjpeg_tx {
+            if now_t.duration_since(mjpeg_t).as_millis() >= MJPEG_FRAME_MSEC {
+                mjpeg_t = now_t;
+                match st7789img.to_jpeg_bytes(MJPEG_QUALITY) {
+                    Ok(jpeg) => {
+                        let _ = frame_tx.send(jpeg);
+                    }
+                    Err(e) => eprintln!("Failed to encode frame: {e}"),
+                }
+            }
         }
 
         thread::sleep(Duration::from_millis(interval));
     }
-    #[allow(unreachable_code)]
+
+    if let Err(e) = save_snapshot(SNAPSHOT_FILE, &state.to_snapshot()) {
+        eprintln!("Warning: failed to save snapshot: {e}");
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mean(v: &[f32]) -> f32 {
+        v.iter().sum::<f32>() / v.len() as f32
+    }
+
+    #[test]
+    fn high_pass_removes_dc() {
+        let mut sp = SpInfo::new(-1, 0, DEF_SP_SAMPLE_RATE, SpMode::Log);
+        let n = sp.signal.len();
+        for (i, x) in sp.signal.iter_mut().enumerate() {
+            // 32 cycles in the window over 0.5 DC offset
+            *x = 0.5 + 0.25 * (2.0 * std::f32::consts::PI * 32.0 * i as f32 / n as f32).sin();
+        }
+        assert!((mean(&sp.signal) - 0.5).abs() < 0.01);
+        sp.apply_high_pass(DEF_SP_HP_ALPHA);
+        assert!(mean(&sp.signal).abs() < 0.01);
+    }
+
+    #[test]
+    fn high_pass_restarts_each_window() {
+        let mut sp = SpInfo::new(-1, 0, DEF_SP_SAMPLE_RATE, SpMode::Log);
+        sp.signal.fill(-0.5);
+        sp.apply_high_pass(DEF_SP_HP_ALPHA);
+        // Not contiguous with the previous window: no step transient
+        sp.signal.fill(0.5);
+        sp.apply_high_pass(DEF_SP_HP_ALPHA);
+        assert!(sp.signal.iter().all(|&x| x == 0.0));
+    }
+
+    fn push_samples(ring: &mut RingSignal16Buffer, samples: &[i16]) {
+        for s in samples {
+            let tail = ring.tail as usize;
+            ring.buffer[tail..tail + 2].copy_from_slice(&s.to_le_bytes());
+            ring.after_read(2);
+        }
+    }
+
+    #[test]
+    fn ring_buffer_read_into_f32_slice() {
+        // 8 samples
+        let mut ring = RingSignal16Buffer::new(8);
+        let mut out = [0.0f32; 4];
+        assert!(!ring.read_into_f32_slice(4, &mut out, 1));
+
+        push_samples(&mut ring, &[32767, -32767, 16384, 0]);
+        assert!(ring.read_into_f32_slice(4, &mut out, 1));
+        assert_eq!(out, [1.0, -1.0, 16384.0 / 32767.0, 0.0]);
+
+        // The last 4 samples are split at the end of the buffer
+        push_samples(&mut ring, &[1, 2, 3, 4, 5, -6]);
+        assert!(ring.read_into_f32_slice(4, &mut out, 1));
+        assert_eq!(
+            out,
+            [3.0 / 32767.0, 4.0 / 32767.0, 5.0 / 32767.0, -6.0 / 32767.0]
+        );
+    }
+
+    fn info_with_status(status: &str) -> Info {
+        Info {
+            status: status.to_string(),
+            ..Info::new()
+        }
+    }
+
+    #[test]
+    fn info_status_helpers() {
+        for (status, playing, paused, stopped) in [
+            ("play", true, false, false),
+            ("pause", false, true, false),
+            ("stop", false, false, true),
+            ("Playing", false, false, false),
+        ] {
+            let info = info_with_status(status);
+            assert_eq!(
+                (info.is_playing(), info.is_paused(), info.is_stopped()),
+                (playing, paused, stopped),
+                "{status}"
+            );
+            assert_eq!(info.is_active(), playing || paused, "{status}");
+        }
+    }
+
+    #[test]
+    fn display_quality_strings() {
+        let quality = |track_type: &str, samplerate: &str, bitdepth: &str, bitrate| {
+            Info {
+                track_type: track_type.to_string(),
+                samplerate: samplerate.to_string(),
+                bitdepth: bitdepth.to_string(),
+                bitrate,
+                ..Info::new()
+            }
+            .display_quality_string()
+        };
+        assert_eq!(quality("mp3", "44.1 kHz", "", Some(320)), "320k MP3");
+        assert_eq!(quality("flac", "96 kHz", "24 bit", None), "96/24 FLAC");
+        assert_eq!(quality("dsf", "2.82 MHz", "1 bit", None), "DSD64");
+        // No sample rate (e.g. web radio without stream info)
+        assert_eq!(quality("webradio", "", "", None), "WEBRADIO");
+    }
+
+    #[test]
+    fn hinted_layout_snaps_glyphs_to_pixels() {
+        let Some(font) = State::load_font(&[FALLBACK_FONT.to_string()]) else {
+            eprintln!("Skipped: {FALLBACK_FONT} not found");
+            return;
+        };
+        let text = "Volumio - Hinting";
+        let plain = State::layout_glyphs(&font, &[], text, Scale::uniform(14.0), false);
+        let hinted = State::layout_glyphs(&font, &[], text, Scale::uniform(14.0), true);
+        assert_eq!(plain.len(), hinted.len());
+        assert!(plain.iter().any(|g| g.position().x.fract() != 0.0));
+        for (i, (p, h)) in plain.iter().zip(&hinted).enumerate() {
+            let (p, h) = (p.position(), h.position());
+            assert!(h.x.fract() == 0.0 && h.y.fract() == 0.0);
+            // Each advance and kerning moves by at most 0.5px
+            assert!((p.x - h.x).abs() <= i as f32);
+        }
+
+        // For visual comparison
+        for (hint, name) in [(false, "plain"), (true, "hinted")] {
+            let img = State::get_text_img(
+                &font,
+                &[],
+                text,
+                Scale::uniform(14.0),
+                COLOR_WHITE,
+                COLOR_BLACK,
+                hint,
+            )
+            .unwrap();
+            let path = std::env::temp_dir().join(format!("st7789volumio_text_{name}.png"));
+            img.save(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn text_size_without_bounding_box() {
+        let Some(font) = State::load_font(&[FALLBACK_FONT.to_string()]) else {
+            eprintln!("Skipped: {FALLBACK_FONT} not found");
+            return;
+        };
+        let scale = Scale::uniform(14.0);
+        let (w, _) = State::calc_text_size(&font, &[], "Volumio", scale, true);
+        let (w_space, _) = State::calc_text_size(&font, &[], "Volumio ", scale, true);
+        assert_eq!(w, w_space);
+        let (w_blank, h_blank) = State::calc_text_size(&font, &[], "  ", scale, true);
+        assert!(w_blank > 0 && h_blank > 0);
+    }
+}